@@ -5,13 +5,20 @@ void setup() {
     Serial.begin(115200);
 }
 
-uint8_t buffer[512];
+uint8_t buffer[FRAME_MAX_PAYLOAD + 1];
 
 void loop() {
     if (Serial.available()) {
-        size_t read_amnt = Serial.readBytes(buffer, 512);
+        int read_amnt = read_frame(buffer);
+        if (read_amnt < 0) {
+            write_nak();
+            return;
+        }
+        if (read_amnt == 0) {
+            return;
+        }
         Operation op(buffer, read_amnt);
-        if (op.kind == OpKind::Sensor) {
+        if (op.kind == OpKind::Sensor || op.kind == OpKind::Scan) {
             uint64_t rsw_data = __UINT32_MAX__; // Get sensor data
             write_sensor_data(rsw_data);
         } else if (op.kind == OpKind::Magnet) {
@@ -20,12 +27,17 @@ void loop() {
         } else if (op.kind == OpKind::Led) {
             delay(100);
             write_ack();
+        } else if (op.kind == OpKind::Handshake) {
+            write_handshake(CAP_SENSORS | CAP_MAGNET | CAP_LEDS, 64);
+        } else if (op.kind == OpKind::Noop || op.kind == OpKind::Subscribe) {
+            // no sensor events to send, CAP_EVENTS is not set
+            write_nak();
         }
     }
 }
 
-size_t write_sensor_data(const uint64_t value) {
-    Serial.write((uint8_t *) &value, sizeof(value));
+void write_sensor_data(const uint64_t value) {
+    write_frame(SENSOR, (const uint8_t *) &value, sizeof(value));
 }
 
 
@@ -6,11 +6,43 @@
 #define SENSOR    0x01
 #define MAGNET    0x02
 #define LED       0x03
+#define SCAN      0x04
+#define SUBSCRIBE 0x05
+#define EVENT     0x06
 
 #define HANDSHAKE 0x10
 #define ACK       0x20
+#define NAK       0x21
 #define QUIT      0xFF
 
+/*
+ * Framing: FRAME_START, opcode, payload length (u16 LE), payload, then the
+ * CRC-16/CCITT-FALSE of the opcode, length and payload (u16 LE).
+ */
+#define FRAME_START       0x7E
+#define FRAME_MAX_PAYLOAD 504
+
+/*
+ * Handshake: the host sends HANDSHAKE with its protocol version, and the
+ * firmware answers HANDSHAKE with its protocol version, the CAP_* flags it
+ * supports, its LED count, then the board's files and ranks.
+ */
+#define FIRMWARE_PROTOCOL_VERSION 1
+#define CAP_SENSORS               0x01
+#define CAP_MAGNET                0x02
+#define CAP_LEDS                  0x04
+#define CAP_EVENTS                0x08
+
+/*
+ * Sensor events: once the host sends SUBSCRIBE with a payload of 1, the
+ * firmware sends an EVENT frame for each reed switch that settles in a new
+ * state, with the square (0 for a1 to 63 for h8, as in SENSOR), 1 if a piece
+ * was placed or 0 if it was lifted, then the time it was first seen in
+ * milliseconds since boot (u32 LE). Changes made by the magnet carriage and
+ * those already reported by SENSOR or SCAN are not sent again. SUBSCRIBE
+ * with a payload of 0 stops the events.
+ */
+
 
 
 /**
@@ -21,6 +53,9 @@ typedef enum {
     Sensor, 
     Magnet, 
     Led, 
+    Scan, 
+    Handshake, 
+    Subscribe, 
     Noop, 
     Quit
 } OpKind; 
\ No newline at end of file
@@ -13,6 +13,16 @@ int rsw_in_pin[8]  = { 30, 31, 32, 33, 34, 35, 36, 37 };
 int rsw_out_pin[8] = { 47, 46, 45, 44, 43, 42, 41, 40 };
 // Reed Switch State
 bool rsw_state[8][8] = { 0 };
+// Sensor events: how many scans in a row a new reading must hold before it
+// is reported, at about 80ms a scan
+#define EVENT_STABLE_SCANS 3
+bool     events_subscribed = false;
+// The reading the host last heard about
+uint64_t reported_rsw_data = 0;
+// The latest reading, and since when and for how many scans it has held
+uint64_t pending_rsw_data  = 0;
+uint32_t pending_since     = 0;
+uint8_t  pending_scans     = 0;
 
 
 // ====================== CoreXY Configuration ======================
@@ -99,7 +109,7 @@ void setup() {
 }
 
 
-uint8_t buffer[512];
+uint8_t buffer[FRAME_MAX_PAYLOAD + 1];
 
 // void loop() {
 //     serial_input_demo();
@@ -111,8 +121,21 @@ uint8_t buffer[512];
 * It receive operation from serial
 */
 void loop() {
+    if (!Serial.available() && events_subscribed) {
+        poll_events();
+        return;
+    }
     if (Serial.available()) {
-        size_t read_amnt = Serial.readBytes(buffer, 512);
+        int read_amnt = read_frame(buffer);
+        if (read_amnt < 0) {
+            // Corrupt frame, ask for it again
+            write_nak();
+            return;
+        }
+        if (read_amnt == 0) {
+            // Only noise, wait for the next frame
+            return;
+        }
         Operation op(buffer, read_amnt);
 
         if (op.kind == OpKind::Sensor) {
@@ -150,6 +173,22 @@ void loop() {
             }
             // print_uint64_t(rsw_data); // For Debugging, Please comment out
             write_sensor_data(rsw_data);
+            reset_events(rsw_data);
+
+        } else if (op.kind == OpKind::Scan) {
+            // Report the current reading straight away, for checking the
+            // board is set up before a game
+            uint64_t rsw_data;
+            uint64_t prev_rsw_data;
+            rsw_state_update();
+            rsw_data = rsw_state_to_uint64();
+            do {
+                prev_rsw_data = rsw_data;
+                rsw_state_update();
+                rsw_data = rsw_state_to_uint64();
+            } while (rsw_data != prev_rsw_data);
+            write_sensor_data(rsw_data);
+            reset_events(rsw_data);
 
         } else if (op.kind == OpKind::Magnet) {
             // CoreXY Movement
@@ -179,6 +218,9 @@ void loop() {
                 delay(100); // Optional delay
             }
             magnet_off();
+            // the carriage moved the pieces, which the host already knows
+            rsw_state_update();
+            reset_events(rsw_state_to_uint64());
             write_ack();
 
         } else if (op.kind == OpKind::Led) {
@@ -199,6 +241,22 @@ void loop() {
             FastLED.show();
             // Send the Acknowledgement Code
             write_ack();
+        } else if (op.kind == OpKind::Handshake) {
+            // Only reached after calibration, so the host knows moves are safe
+            write_handshake(CAP_SENSORS | CAP_MAGNET | CAP_LEDS | CAP_EVENTS, NUM_LEDS);
+        } else if (op.kind == OpKind::Subscribe) {
+            if (op.data_len() != 1) {
+                write_nak();
+                return;
+            }
+            events_subscribed = op.data[0] != 0;
+            // only changes from here on are news to the host
+            rsw_state_update();
+            reset_events(rsw_state_to_uint64());
+            write_ack();
+        } else if (op.kind == OpKind::Noop) {
+            // Unknown instruction, nothing was done
+            write_nak();
         }
     }
 }
@@ -208,8 +266,47 @@ CRGB bytes_to_crgb(const uint8_t* base) {
     return CRGB(base[0], base[1], base[2]); 
 }
 
-size_t write_sensor_data(const uint64_t &value) {
-    Serial.write((uint8_t *) &value, sizeof(value));
+void write_sensor_data(const uint64_t &value) {
+    write_frame(SENSOR, (const uint8_t *) &value, sizeof(value));
+}
+
+/*
+* Take `rsw_data` as what the host knows, so only later changes are
+* reported as events
+*/
+void reset_events(uint64_t rsw_data) {
+    reported_rsw_data = rsw_data;
+    pending_rsw_data = rsw_data;
+    pending_scans = EVENT_STABLE_SCANS;
+}
+
+/*
+* Scan the reed switches once, and send an EVENT for each one that changed
+* once the new reading has held for EVENT_STABLE_SCANS scans
+*/
+void poll_events() {
+    rsw_state_update();
+    uint64_t rsw_data = rsw_state_to_uint64();
+    if (rsw_data != pending_rsw_data) {
+        // still bouncing, start counting again
+        pending_rsw_data = rsw_data;
+        pending_since = millis();
+        pending_scans = 1;
+        return;
+    }
+    if (pending_scans < EVENT_STABLE_SCANS) {
+        pending_scans++;
+        if (pending_scans < EVENT_STABLE_SCANS) {
+            return;
+        }
+    }
+    uint64_t changed = rsw_data ^ reported_rsw_data;
+    for (uint8_t square = 0; square < 64; square++) {
+        if ((changed >> square) & 1) {
+            write_event(square, (rsw_data >> square) & 1, pending_since);
+        }
+    }
+    reported_rsw_data = rsw_data;
 }
 
 uint8_t msb(uint64_t num) {
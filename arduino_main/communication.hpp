@@ -9,12 +9,42 @@
 #define SENSOR    0x01
 #define MAGNET    0x02
 #define LED       0x03
+#define SCAN      0x04
+#define SUBSCRIBE 0x05
+#define EVENT     0x06
 
 #define HANDSHAKE 0x10
 #define ACK       0x20
+#define NAK       0x21
 #define QUIT      0xFF
 
-const unsigned char __ACK = ACK; 
+/*
+ * Framing: FRAME_START, opcode, payload length (u16 LE), payload, then the
+ * CRC-16/CCITT-FALSE of the opcode, length and payload (u16 LE).
+ */
+#define FRAME_START       0x7E
+#define FRAME_MAX_PAYLOAD 504
+
+/*
+ * Handshake: the host sends HANDSHAKE with its protocol version, and the
+ * firmware answers HANDSHAKE with its protocol version, the CAP_* flags it
+ * supports, its LED count, then the board's files and ranks.
+ */
+#define FIRMWARE_PROTOCOL_VERSION 1
+#define CAP_SENSORS               0x01
+#define CAP_MAGNET                0x02
+#define CAP_LEDS                  0x04
+#define CAP_EVENTS                0x08
+
+/*
+ * Sensor events: once the host sends SUBSCRIBE with a payload of 1, the
+ * firmware sends an EVENT frame for each reed switch that settles in a new
+ * state, with the square (0 for a1 to 63 for h8, as in SENSOR), 1 if a piece
+ * was placed or 0 if it was lifted, then the time it was first seen in
+ * milliseconds since boot (u32 LE). Changes made by the magnet carriage and
+ * those already reported by SENSOR or SCAN are not sent again. SUBSCRIBE
+ * with a payload of 0 stops the events.
+ */
 
 /**
  * @brief
@@ -24,6 +54,9 @@ enum OpKind {
     Sensor, 
     Magnet, 
     Led, 
+    Scan, 
+    Handshake, 
+    Subscribe, 
     Noop, 
     Quit
 }; 
@@ -43,6 +76,12 @@ enum OpKind get_opkind(const unsigned char* serial_read_buffer) {
             return Magnet; 
         case LED: 
             return Led; 
+        case SCAN: 
+            return Scan; 
+        case HANDSHAKE: 
+            return Handshake; 
+        case SUBSCRIBE: 
+            return Subscribe; 
         case QUIT: 
             return Quit; 
         default: 
@@ -98,41 +137,118 @@ CRGB* Operation::try_into_CRGB() {
     return new CRGB(data[0], data[1], data[2]);  
 }
 
-void write_ack() {
-    Serial.write(__ACK); 
+/**
+ * @brief
+ * Continues the CRC-16/CCITT-FALSE `crc` over `len` bytes at `data`.
+ * Start from 0xFFFF.
+ */
+uint16_t crc16(uint16_t crc, const uint8_t* data, size_t len) {
+    for (size_t i = 0; i < len; i++) {
+        crc ^= (uint16_t) data[i] << 8;
+        for (uint8_t bit = 0; bit < 8; bit++) {
+            crc = (crc & 0x8000) ? (crc << 1) ^ 0x1021 : crc << 1;
+        }
+    }
+    return crc;
 }
 
 /**
- * Perform handshake with Raspi host *once*. 
- * 
- * @return true if handshake is successful. 
- * @return false otherwise. 
+ * @brief
+ * Reads one frame into `buffer` as the opcode followed by the payload,
+ * skipping anything before the start byte. `buffer` must have room for
+ * `FRAME_MAX_PAYLOAD + 1` bytes.
+ *
+ * @return Length of the instruction in `buffer`, to build an `Operation` from.
+ * @return 0 if no frame arrived.
+ * @return -1 if a frame arrived but was cut short or corrupted, which the
+ *         host should be told about with `write_nak()`.
  */
-bool handshake() {
-    /* Listen for PC initiation */
-    if (Serial.available()) {
-        size_t available_amnt = Serial.available(); 
-
-        uint8_t buffer[available_amnt] {0}; 
-        uint8_t arduino_id; 
-
-        Serial.readBytes(buffer, available_amnt); 
-        if (available_amnt == 2 && buffer[0] == HANDSHAKE) {
-            // buffer[1..] should be Arduino ID, which is assumed to be non-zero
-            arduino_id = buffer[1]; 
-            Serial.write(buffer, available_amnt); 
-        }
-        /* Wait for PC readback */
-        while (true) {
-            if (Serial.available()) {
-                memset(buffer, 0, available_amnt); 
-                Serial.readBytes(buffer, available_amnt); 
-                if (buffer[0] == HANDSHAKE && buffer[1] == arduino_id) {
-                    Serial.write(__ACK); 
-                    return true; 
-                }
-            }
+int read_frame(uint8_t* buffer) {
+    int byte;
+    do {
+        byte = Serial.read();
+        if (byte < 0) {
+            return 0;
         }
+    } while (byte != FRAME_START);
+
+    uint8_t header[3];
+    if (Serial.readBytes(header, 3) != 3) {
+        return -1;
     }
-    return false; 
+    size_t payload_len = header[1] | ((size_t) header[2] << 8);
+    if (payload_len > FRAME_MAX_PAYLOAD) {
+        return -1;
+    }
+    buffer[0] = header[0];
+    if (Serial.readBytes(buffer + 1, payload_len) != payload_len) {
+        return -1;
+    }
+    uint8_t trailer[2];
+    if (Serial.readBytes(trailer, 2) != 2) {
+        return -1;
+    }
+    uint16_t crc = crc16(crc16(0xFFFF, header, 3), buffer + 1, payload_len);
+    if (crc != (trailer[0] | ((uint16_t) trailer[1] << 8))) {
+        return -1;
+    }
+    return payload_len + 1;
+}
+
+/**
+ * @brief
+ * Writes `payload_len` bytes at `payload` to the host as one frame.
+ */
+void write_frame(uint8_t opcode, const uint8_t* payload, size_t payload_len) {
+    uint8_t header[4] {
+        FRAME_START, opcode, (uint8_t) payload_len, (uint8_t) (payload_len >> 8)
+    };
+    uint16_t crc = crc16(crc16(0xFFFF, header + 1, 3), payload, payload_len);
+    uint8_t trailer[2] { (uint8_t) crc, (uint8_t) (crc >> 8) };
+
+    Serial.write(header, sizeof(header));
+    Serial.write(payload, payload_len);
+    Serial.write(trailer, sizeof(trailer));
+}
+
+void write_ack() {
+    write_frame(ACK, NULL, 0);
+}
+
+/**
+ * @brief
+ * Tells the host its last instruction was not carried out and can be resent.
+ */
+void write_nak() {
+    write_frame(NAK, NULL, 0);
+}
+
+/**
+ * @brief
+ * Answers the host's HANDSHAKE, once ready to take instructions, with the
+ * protocol version and what this board can do.
+ *
+ * @param caps      The CAP_* flags of the board.
+ * @param led_count Number of LEDs, 0 if there are none.
+ */
+void write_handshake(uint8_t caps, uint8_t led_count) {
+    uint8_t payload[5] { FIRMWARE_PROTOCOL_VERSION, caps, led_count, 8, 8 };
+    write_frame(HANDSHAKE, payload, sizeof(payload));
+}
+
+/**
+ * @brief
+ * Tells a subscribed host that the reed switch on `square` settled.
+ *
+ * @param square  0 for a1 to 63 for h8, the bit of the square in SENSOR.
+ * @param placed  Whether a piece was placed rather than lifted.
+ * @param seen_at When the change was first seen, from `millis()`.
+ */
+void write_event(uint8_t square, bool placed, uint32_t seen_at) {
+    uint8_t payload[6] {
+        square, (uint8_t) placed,
+        (uint8_t) seen_at, (uint8_t) (seen_at >> 8),
+        (uint8_t) (seen_at >> 16), (uint8_t) (seen_at >> 24)
+    };
+    write_frame(EVENT, payload, sizeof(payload));
 }
\ No newline at end of file
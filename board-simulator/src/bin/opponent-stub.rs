@@ -0,0 +1,122 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+//! A stand-in for opponent-wrapper that replays a fixed game.
+//!
+//! master-program starts it in place of the wrapper. The whole game is given
+//! as UCI moves in `FLAGFALL_STUB_MOVES` and the user's side as `white` or
+//! `black` in `FLAGFALL_STUB_COLOUR`. The stub plays the other side's moves
+//! and checks that the user's moves arrive as scripted, ending the game once
+//! the moves run out.
+
+use clap::Parser;
+use flagfall_core::variant::StartPosition;
+use flagfall_protocol::{
+    read_message, write_message, Colour, OpponentMessage, PlayerMessage, ProtocolError,
+};
+use log::{error, info, warn};
+use shakmaty::{fen::Fen, san::San, uci::Uci, Chess, Color, Position};
+
+const MOVES_VAR: &str = "FLAGFALL_STUB_MOVES";
+const COLOUR_VAR: &str = "FLAGFALL_STUB_COLOUR";
+
+/// The arguments master-program starts opponent-wrapper with.
+#[derive(Parser)]
+#[clap(author, version, about)]
+struct Cli {
+    // always given, the stub is the only "engine" there is
+    #[clap(short, long)]
+    #[allow(dead_code)]
+    engine: bool,
+    #[clap(long, value_name = "INDEX")]
+    chess960: Option<u32>,
+    #[clap(long, value_name = "FEN", conflicts_with = "chess960")]
+    fen: Option<Fen>,
+}
+
+fn send(message: &OpponentMessage) {
+    if let Err(e) = write_message(&mut std::io::stdout().lock(), message) {
+        error!("failed to send {message:?}: {e}");
+    }
+}
+
+/// Waits for the user's next move, or `None` if master-program quits.
+fn recv_move() -> Option<String> {
+    loop {
+        let read = read_message(&mut std::io::stdin().lock());
+        match read {
+            Ok(Some(PlayerMessage::Move { uci })) => return Some(uci),
+            Ok(Some(PlayerMessage::Quit) | None) => return None,
+            Ok(Some(message)) => warn!("ignoring {message:?}"),
+            Err(e @ ProtocolError::Malformed { .. }) => warn!("ignoring {e}"),
+            Err(e) => {
+                error!("{e}");
+                return None;
+            }
+        }
+    }
+}
+
+fn main() {
+    env_logger::init();
+    let args = Cli::parse();
+
+    let start = match (args.chess960, args.fen) {
+        (Some(index), _) => StartPosition::Chess960(index),
+        (None, Some(fen)) => StartPosition::Fen(fen),
+        (None, None) => StartPosition::Standard,
+    };
+    let mut pos: Chess = match start.to_position() {
+        Ok(pos) => pos,
+        Err(e) => {
+            send(&OpponentMessage::Error {
+                message: format!("cannot start a game: {e}"),
+            });
+            return;
+        }
+    };
+    let moves = std::env::var(MOVES_VAR).unwrap_or_default();
+    let user = match std::env::var(COLOUR_VAR).as_deref() {
+        Ok("black") => Color::Black,
+        _ => Color::White,
+    };
+    send(&OpponentMessage::GameStart {
+        colour: user.fold_wb(Colour::White, Colour::Black),
+    });
+
+    for uci in moves.split_whitespace() {
+        let Ok(Ok(mv)) = uci.parse::<Uci>().map(|uci| uci.to_move(&pos)) else {
+            send(&OpponentMessage::Error {
+                message: format!("scripted move {uci} is not legal"),
+            });
+            return;
+        };
+        if pos.turn() == user {
+            match recv_move() {
+                Some(played) if played == uci => info!("user played {uci} as scripted"),
+                Some(played) => {
+                    send(&OpponentMessage::Error {
+                        message: format!("expected the user to play {uci}, got {played}"),
+                    });
+                    return;
+                }
+                None => return,
+            }
+        } else {
+            send(&OpponentMessage::Move {
+                uci: uci.to_string(),
+                san: San::from_move(&pos, &mv).to_string(),
+            });
+        }
+        pos.play_unchecked(&mv);
+    }
+
+    if pos.is_game_over() {
+        // master-program sees the result itself and quits
+        let _ = recv_move();
+    } else {
+        send(&OpponentMessage::GameEnd {
+            winner: None,
+            reason: "script finished".to_string(),
+        });
+    }
+}
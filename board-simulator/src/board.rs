@@ -0,0 +1,339 @@
+//! A model of the board firmware in `arduino_main`.
+//!
+//! Instructions and replies are the contents of a frame (see
+//! `serial_communicator::frame`): an opcode from `arduino_comms/opcode.h`
+//! followed by its payload.
+
+use std::collections::VecDeque;
+use std::time::Instant;
+
+use flagfall_protocol::{MagnetStep, LED_COUNT};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use serial_communicator::events::{SwitchChange, SwitchEvent};
+use serial_communicator::handshake::{BoardInfo, PROTOCOL_VERSION};
+use shakmaty::{Bitboard, File, Rank, Square};
+
+use crate::script::SensorEvent;
+
+pub const SENSOR: u8 = 0x01;
+pub const MAGNET: u8 = 0x02;
+pub const LED: u8 = 0x03;
+pub const SCAN: u8 = 0x04;
+pub const SUBSCRIBE: u8 = 0x05;
+pub const EVENT: u8 = 0x06;
+pub const HANDSHAKE: u8 = 0x10;
+pub const ACK: u8 = 0x20;
+pub const NAK: u8 = 0x21;
+pub const QUIT: u8 = 0xFF;
+
+/// How far off a square's centre the carriage may stop and still be on it.
+const SQUARE_TOLERANCE: f32 = 0.25;
+/// How close a carried piece may pass to a standing piece's centre before
+/// they knock together.
+const COLLISION_DISTANCE: f32 = 0.35;
+/// How finely a carried piece's path is checked for collisions.
+const PATH_RESOLUTION: f32 = 0.05;
+
+/// Everything the simulated board saw during a run.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    /// The final reed switch occupancy.
+    pub occupancy: u64,
+    /// Where pieces were left off the 64 squares, such as in the graveyards.
+    pub parked: Vec<(f32, f32)>,
+    /// Every `LED` frame, from h8 to a1.
+    pub led_frames: Vec<Vec<u32>>,
+    /// Every `MAGNET` instruction's steps.
+    pub magnet_moves: Vec<Vec<MagnetStep>>,
+    /// Where a carried piece ran into a standing one.
+    pub collisions: Vec<(f32, f32)>,
+}
+
+/// The simulated board: which squares hold pieces, where the magnet carriage
+/// is, and what it has been asked to do.
+#[derive(Debug)]
+pub struct SimulatedBoard {
+    occupancy: Bitboard,
+    carriage: (f32, f32),
+    script: VecDeque<Vec<SensorEvent>>,
+    /// Whether the host asked for `EVENT` frames instead of `SENSOR` reads.
+    subscribed: bool,
+    /// When the board was switched on, for event timestamps.
+    booted: Instant,
+    report: Report,
+}
+
+impl SimulatedBoard {
+    /// Creates a board with pieces on `occupancy` whose player will make the
+    /// changes in `script`, one line per `SENSOR` read.
+    #[must_use]
+    pub fn new(occupancy: Bitboard, script: Vec<Vec<SensorEvent>>) -> Self {
+        Self {
+            occupancy,
+            // the firmware parks the carriage on a1 after calibrating
+            carriage: (1.0, 1.0),
+            script: script.into(),
+            subscribed: false,
+            booted: Instant::now(),
+            report: Report::default(),
+        }
+    }
+
+    #[must_use]
+    pub const fn occupancy(&self) -> Bitboard {
+        self.occupancy
+    }
+
+    /// Whether the host wants the player's changes pushed as `EVENT` frames,
+    /// see [`Self::next_events`].
+    #[must_use]
+    pub const fn subscribed(&self) -> bool {
+        self.subscribed
+    }
+
+    /// The number of `SENSOR` reads the script has left.
+    #[must_use]
+    pub fn remaining_reads(&self) -> usize {
+        self.script.len()
+    }
+
+    #[must_use]
+    pub fn report(&self) -> Report {
+        Report {
+            occupancy: self.occupancy.0,
+            ..self.report.clone()
+        }
+    }
+
+    /// Carries out `instruction` like the firmware would, returning the reply
+    /// to frame, which is empty if there is none.
+    ///
+    /// Returns `None` for a `SENSOR` read once the script has run out, where
+    /// the real board would wait forever for the player.
+    pub fn handle(&mut self, instruction: &[u8]) -> Option<Vec<u8>> {
+        let Some((&opcode, payload)) = instruction.split_first() else {
+            return Some(Vec::new());
+        };
+        match opcode {
+            SENSOR => {
+                let events = self.script.pop_front()?;
+                for event in events {
+                    match event {
+                        SensorEvent::Lift(square) => self.occupancy.discard(square),
+                        SensorEvent::Place(square) => self.occupancy.add(square),
+                    }
+                }
+                debug!("sensor read: {:x}", self.occupancy.0);
+                Some(self.sensor_reply())
+            }
+            SCAN => Some(self.sensor_reply()),
+            SUBSCRIBE => match payload {
+                &[enabled @ (0 | 1)] => {
+                    self.subscribed = enabled == 1;
+                    Some(vec![ACK])
+                }
+                _ => Some(vec![NAK]),
+            },
+            HANDSHAKE => {
+                #[allow(clippy::cast_possible_truncation)]
+                let info = BoardInfo {
+                    version: PROTOCOL_VERSION,
+                    sensors: true,
+                    magnet: true,
+                    led_count: LED_COUNT as u8,
+                    events: true,
+                    files: 8,
+                    ranks: 8,
+                };
+                let mut reply = vec![HANDSHAKE];
+                reply.extend(info.to_payload());
+                Some(reply)
+            }
+            MAGNET => {
+                // like the firmware, a trailing partial step is dropped
+                let steps = payload
+                    .chunks_exact(9)
+                    .map(|chunk| MagnetStep {
+                        x: f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]),
+                        y: f32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]),
+                        magnet: chunk[8] != 0,
+                    })
+                    .collect::<Vec<_>>();
+                self.run_steps(&steps);
+                self.report.magnet_moves.push(steps);
+                Some(vec![ACK])
+            }
+            LED => {
+                let frame = payload
+                    .chunks_exact(3)
+                    .map(|rgb| u32::from_be_bytes([0, rgb[0], rgb[1], rgb[2]]))
+                    .collect::<Vec<_>>();
+                if frame.len() != LED_COUNT {
+                    warn!("LED frame has {} colours", frame.len());
+                }
+                self.report.led_frames.push(frame);
+                Some(vec![ACK])
+            }
+            _ => {
+                // the firmware does nothing for anything else and says so
+                warn!("rejecting instruction {instruction:x?}");
+                Some(vec![NAK])
+            }
+        }
+    }
+
+    /// Makes the changes of the script's next line, returning an `EVENT`
+    /// frame's contents for each, or `None` once the script has run out.
+    pub fn next_events(&mut self) -> Option<Vec<Vec<u8>>> {
+        let events = self.script.pop_front()?;
+        let timestamp = self.booted.elapsed();
+        let frames = events
+            .into_iter()
+            .map(|event| {
+                let (square, change) = match event {
+                    SensorEvent::Lift(square) => {
+                        self.occupancy.discard(square);
+                        (square, SwitchChange::Lifted)
+                    }
+                    SensorEvent::Place(square) => {
+                        self.occupancy.add(square);
+                        (square, SwitchChange::Placed)
+                    }
+                };
+                let mut frame = vec![EVENT];
+                frame.extend(
+                    SwitchEvent {
+                        square,
+                        change,
+                        timestamp,
+                    }
+                    .to_payload(),
+                );
+                frame
+            })
+            .collect();
+        debug!("pushed events, now {:x}", self.occupancy.0);
+        Some(frames)
+    }
+
+    /// Sensor readings are always sent back as `SENSOR` frames.
+    fn sensor_reply(&self) -> Vec<u8> {
+        let mut reply = vec![SENSOR];
+        reply.extend_from_slice(&self.occupancy.0.to_le_bytes());
+        reply
+    }
+
+    /// Moves the carriage through `steps`, picking up the piece under it when
+    /// the magnet engages and putting it down when the magnet releases.
+    fn run_steps(&mut self, steps: &[MagnetStep]) {
+        let mut carrying = false;
+        for step in steps {
+            if step.magnet && !carrying {
+                carrying = self.pick_up(self.carriage);
+            } else if !step.magnet && carrying {
+                self.put_down(self.carriage);
+                carrying = false;
+            }
+            let target = (step.x, step.y);
+            if carrying {
+                self.check_path(self.carriage, target);
+            }
+            self.carriage = target;
+        }
+        // the firmware switches the magnet off once it is done
+        if carrying {
+            self.put_down(self.carriage);
+        }
+    }
+
+    fn pick_up(&mut self, at: (f32, f32)) -> bool {
+        if let Some(square) = square_at(at) {
+            if self.occupancy.contains(square) {
+                self.occupancy.discard(square);
+                return true;
+            }
+        }
+        let parked = &mut self.report.parked;
+        if let Some(index) = parked
+            .iter()
+            .position(|&spot| distance(spot, at) <= SQUARE_TOLERANCE)
+        {
+            parked.swap_remove(index);
+            return true;
+        }
+        warn!("magnet engaged at {at:?} with no piece under it");
+        false
+    }
+
+    fn put_down(&mut self, at: (f32, f32)) {
+        match square_at(at) {
+            Some(square) if self.occupancy.contains(square) => {
+                warn!("piece put down on occupied {square}");
+                self.report.collisions.push(at);
+            }
+            Some(square) => self.occupancy.add(square),
+            None => self.report.parked.push(at),
+        }
+    }
+
+    /// Records a collision wherever the straight path from `from` to `to`
+    /// passes over a standing piece.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    fn check_path(&mut self, from: (f32, f32), to: (f32, f32)) {
+        let samples = (distance(from, to) / PATH_RESOLUTION).ceil() as u32;
+        let mut hit: Option<Square> = None;
+        for i in 0..=samples {
+            let t = if samples == 0 {
+                1.0
+            } else {
+                i as f32 / samples as f32
+            };
+            let point = (
+                (to.0 - from.0).mul_add(t, from.0),
+                (to.1 - from.1).mul_add(t, from.1),
+            );
+            let blocker = self
+                .occupancy
+                .into_iter()
+                .find(|&square| distance(point, square_centre(square)) < COLLISION_DISTANCE);
+            if let Some(square) = blocker.filter(|_| blocker != hit) {
+                warn!("carried piece ran into {square}");
+                self.report.collisions.push(point);
+            }
+            hit = blocker;
+        }
+    }
+}
+
+/// The square whose centre `at` is on, if any.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn square_at(at: (f32, f32)) -> Option<Square> {
+    let (x, y) = (at.0.round(), at.1.round());
+    if (at.0 - x).abs() > SQUARE_TOLERANCE || (at.1 - y).abs() > SQUARE_TOLERANCE {
+        return None;
+    }
+    if !(1.0..=8.0).contains(&x) || !(1.0..=8.0).contains(&y) {
+        return None;
+    }
+    Some(Square::from_coords(
+        File::new(x as u32 - 1),
+        Rank::new(y as u32 - 1),
+    ))
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn square_centre(square: Square) -> (f32, f32) {
+    (
+        u32::from(square.file()) as f32 + 1.0,
+        u32::from(square.rank()) as f32 + 1.0,
+    )
+}
+
+fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
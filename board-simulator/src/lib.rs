@@ -0,0 +1,20 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+//! A stand-in for the Arduino board, so the Flagfall programs can run without
+//! the hardware.
+//!
+//! - [`board`] models the firmware: reed switches, magnet carriage and LEDs.
+//! - [`script`] reads the reed-switch changes the simulated player makes.
+//! - [`pty`] serves a [`board::SimulatedBoard`] over a pseudo-terminal that
+//!   serial-communicator can open like the real device.
+//!
+//! The `opponent-stub` binary likewise stands in for opponent-wrapper,
+//! replaying a fixed game.
+
+pub mod board;
+#[cfg(unix)]
+pub mod pty;
+pub mod script;
+
+pub use board::{Report, SimulatedBoard};
+pub use script::{parse_script, ScriptError, SensorEvent};
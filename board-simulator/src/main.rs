@@ -0,0 +1,68 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+use std::path::PathBuf;
+
+use clap::Parser;
+use shakmaty::fen::Fen;
+
+#[derive(Parser)]
+#[clap(author, version, about)]
+struct Cli {
+    /// The reed-switch changes the player makes, one sensor read per line.
+    #[clap(long, value_name = "FILE")]
+    script: Option<PathBuf>,
+    /// Set up the pieces of this position instead of the standard one.
+    #[clap(long, value_name = "FEN")]
+    fen: Option<Fen>,
+    /// Also make the pseudo-terminal available at this path.
+    #[clap(long, value_name = "PATH")]
+    link: Option<PathBuf>,
+    /// Write the run's report here instead of to stdout.
+    #[clap(long, value_name = "FILE")]
+    report: Option<PathBuf>,
+}
+
+#[cfg(unix)]
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    use board_simulator::{parse_script, pty::PtyBoard, SimulatedBoard};
+    use log::info;
+
+    env_logger::init();
+    let args = Cli::parse();
+
+    let script = match &args.script {
+        Some(path) => parse_script(&std::fs::read_to_string(path)?)?,
+        None => Vec::new(),
+    };
+    let occupancy = args.fen.unwrap_or_default().as_setup().board.occupied();
+    let mut board = SimulatedBoard::new(occupancy, script);
+
+    let mut pty = PtyBoard::open()?;
+    let path = pty.path().ok_or("pseudo-terminal has no path")?;
+    if let Some(link) = &args.link {
+        // a link left over from an earlier run would point at a dead terminal
+        let _ = std::fs::remove_file(link);
+        std::os::unix::fs::symlink(&path, link)?;
+    }
+    println!("{path}");
+    info!("simulated board listening on {path}");
+
+    let served = pty.serve(&mut board);
+    if let Some(link) = &args.link {
+        let _ = std::fs::remove_file(link);
+    }
+    served?;
+
+    let report = serde_json::to_string(&board.report())?;
+    match &args.report {
+        Some(path) => std::fs::write(path, report)?,
+        None => println!("{report}"),
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn main() {
+    let _ = Cli::parse();
+    eprintln!("the board simulator needs pseudo-terminals, which this platform does not have");
+}
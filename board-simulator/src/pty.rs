@@ -0,0 +1,140 @@
+//! Serving a simulated board over a pseudo-terminal.
+
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use serial_communicator::frame::{encode_frame, FrameDecoder};
+use serialport::{SerialPort, TTYPort};
+
+use crate::board::{SimulatedBoard, NAK};
+
+/// How long to wait for more of an instruction before checking again.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(1);
+/// How long the host must have been quiet before the simulated player makes
+/// the next changes, when they are pushed as events. This keeps the player
+/// from moving while the host is still busy with the last changes.
+const THINK_TIME: Duration = Duration::from_millis(200);
+
+/// What came from the client.
+enum Incoming {
+    Instruction(Vec<u8>),
+    /// Nothing for a while.
+    Idle,
+    HungUp,
+}
+
+/// A pseudo-terminal pair with the simulated board on one end.
+pub struct PtyBoard {
+    master: TTYPort,
+    path: Option<String>,
+    // held open so reads on `master` do not fail before a client connects,
+    // then dropped so `master` hangs up once the client closes the device
+    slave: Option<TTYPort>,
+    decoder: FrameDecoder,
+}
+
+impl PtyBoard {
+    /// Opens a new pseudo-terminal pair.
+    ///
+    /// # Errors
+    ///
+    /// Returns the `serialport::Error` if no pseudo-terminal can be opened.
+    pub fn open() -> serialport::Result<Self> {
+        let (master, slave) = TTYPort::pair()?;
+        Ok(Self {
+            master,
+            path: slave.name(),
+            slave: Some(slave),
+            decoder: FrameDecoder::default(),
+        })
+    }
+
+    /// The device path serial-communicator should open.
+    #[must_use]
+    pub fn path(&self) -> Option<String> {
+        self.path.clone()
+    }
+
+    /// Answers instructions with `board` until the client closes the device,
+    /// or a `SENSOR` read finds its script has run out.
+    ///
+    /// Once the client subscribes to events, each line of the script is
+    /// pushed as soon as the client has been quiet for a moment.
+    ///
+    /// # Errors
+    ///
+    /// Returns the `io::Error` if the pseudo-terminal fails.
+    pub fn serve(&mut self, board: &mut SimulatedBoard) -> io::Result<()> {
+        loop {
+            let think_time = board.subscribed().then_some(THINK_TIME);
+            let instruction = match self.read_instruction(think_time)? {
+                Incoming::Instruction(instruction) => instruction,
+                Incoming::Idle => {
+                    for event in board.next_events().unwrap_or_default() {
+                        self.write_reply(&event)?;
+                    }
+                    continue;
+                }
+                Incoming::HungUp => {
+                    info!("client disconnected");
+                    return Ok(());
+                }
+            };
+            // the client has the device open now
+            drop(self.slave.take());
+            debug!("received {instruction:x?}");
+            let Some(reply) = board.handle(&instruction) else {
+                info!("script finished");
+                return Ok(());
+            };
+            self.write_reply(&reply)?;
+        }
+    }
+
+    /// Frames `reply`, an opcode and its payload, unless it is empty.
+    fn write_reply(&mut self, reply: &[u8]) -> io::Result<()> {
+        if let Some((&opcode, payload)) = reply.split_first() {
+            self.master.write_all(&encode_frame(opcode, payload))?;
+            self.master.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Reads the next instruction. Unless `idle_after` is `None`, gives up
+    /// once nothing has arrived for that long.
+    fn read_instruction(&mut self, idle_after: Option<Duration>) -> io::Result<Incoming> {
+        let mut buf = [0_u8; 512];
+        self.master
+            .set_timeout(idle_after.unwrap_or(IDLE_TIMEOUT))?;
+        loop {
+            while let Some(decoded) = self.decoder.next_frame() {
+                match decoded {
+                    Ok(frame) => {
+                        let mut instruction = vec![frame.opcode];
+                        instruction.extend_from_slice(&frame.payload);
+                        return Ok(Incoming::Instruction(instruction));
+                    }
+                    Err(e) => {
+                        // like the firmware, ask for a corrupt instruction again
+                        warn!("rejecting a corrupt frame: {e}");
+                        self.master.write_all(&encode_frame(NAK, &[]))?;
+                        self.master.flush()?;
+                    }
+                }
+            }
+            match self.master.read(&mut buf) {
+                Ok(0) => return Ok(Incoming::HungUp),
+                Ok(n) => self.decoder.push(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => {
+                    // only idle between instructions, not halfway through one
+                    if idle_after.is_some() && self.decoder.pending() == 0 {
+                        return Ok(Incoming::Idle);
+                    }
+                }
+                Err(e) if e.kind() == io::ErrorKind::BrokenPipe => return Ok(Incoming::HungUp),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
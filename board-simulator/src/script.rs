@@ -0,0 +1,71 @@
+//! Scripts of reed-switch changes.
+//!
+//! Each line of a script is what the player does between two sensor reads,
+//! as `lift <square>` and `place <square>` pairs, for example
+//! `lift e2 place e4` or, for a capture seen one toggle at a time, just
+//! `lift d5`. Blank lines and everything after a `#` are ignored.
+
+use std::fmt::Display;
+
+use shakmaty::Square;
+
+/// One reed switch changing.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SensorEvent {
+    /// A piece is lifted off the square.
+    Lift(Square),
+    /// A piece is put down on the square.
+    Place(Square),
+}
+
+/// Why a script could not be read.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ScriptError {
+    /// The 1-based line the problem is on.
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for ScriptError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "script line {}: {}", self.line, self.message)
+    }
+}
+
+impl std::error::Error for ScriptError {}
+
+/// Parses `text` into the changes seen by each sensor read, in order.
+///
+/// # Errors
+///
+/// Returns [`ScriptError`] for an unknown action, a bad square or an action
+/// without a square.
+pub fn parse_script(text: &str) -> Result<Vec<Vec<SensorEvent>>, ScriptError> {
+    let mut reads = Vec::new();
+    for (index, line) in text.lines().enumerate() {
+        let error = |message: String| ScriptError {
+            line: index + 1,
+            message,
+        };
+        let line = line.split('#').next().unwrap_or_default();
+        let mut words = line.split_whitespace();
+        let mut events = Vec::new();
+        while let Some(action) = words.next() {
+            let square = words
+                .next()
+                .ok_or_else(|| error(format!("`{action}` needs a square")))?;
+            let square = square
+                .parse::<Square>()
+                .map_err(|_| error(format!("`{square}` is not a square")))?;
+            events.push(match action {
+                "lift" => SensorEvent::Lift(square),
+                "place" => SensorEvent::Place(square),
+                _ => return Err(error(format!("unknown action `{action}`"))),
+            });
+        }
+        if !events.is_empty() {
+            reads.push(events);
+        }
+    }
+    Ok(reads)
+}
@@ -0,0 +1,115 @@
+#![cfg(unix)]
+
+extern crate board_simulator;
+
+use board_simulator::pty::PtyBoard;
+use board_simulator::{parse_script, SimulatedBoard};
+use flagfall_core::{move_to_steps, Graveyard};
+use flagfall_protocol::LED_COUNT;
+use futures_util::StreamExt;
+use serial_communicator::connection::{BoardConnection, ConnectionOptions};
+use serial_communicator::device::DeviceSelector;
+use serial_communicator::events::{SwitchChange, SwitchEvent};
+use serial_communicator::LedFrame;
+use shakmaty::{uci::Uci, Bitboard, Chess, Position, Square};
+
+fn _options(path: String) -> ConnectionOptions {
+    ConnectionOptions {
+        selector: DeviceSelector {
+            port: Some(path),
+            ..DeviceSelector::default()
+        },
+        ..ConnectionOptions::default()
+    }
+}
+
+#[tokio::test]
+async fn test_connection_drives_the_board() {
+    let start = Chess::default();
+    let script = parse_script("lift e2\nplace e4").unwrap();
+    let mut pty = PtyBoard::open().unwrap();
+    let path = pty.path().unwrap();
+    let server = std::thread::spawn(move || {
+        let mut board = SimulatedBoard::new(Chess::default().board().occupied(), script);
+        pty.serve(&mut board).map(|()| board.report())
+    });
+
+    let mut connection = BoardConnection::open(_options(path)).await.unwrap();
+    assert_eq!(usize::from(connection.info().led_count), LED_COUNT);
+
+    let occupied = start.board().occupied();
+    assert_eq!(connection.scan().await.unwrap(), occupied);
+    let lifted = occupied ^ Bitboard::from_square(Square::E2);
+    assert_eq!(connection.read_sensors().await.unwrap(), lifted);
+    assert_eq!(
+        connection.read_sensors().await.unwrap(),
+        lifted | Bitboard::from_square(Square::E4)
+    );
+
+    let mut colours = vec![0; LED_COUNT];
+    colours[0] = 0xFF_0000;
+    connection
+        .set_leds(LedFrame::new(colours.clone()).unwrap())
+        .await
+        .unwrap();
+
+    let mv = "e2e4".parse::<Uci>().unwrap().to_move(&start).unwrap();
+    let pos = start.play(&mv).unwrap();
+    let mv = "d7d5".parse::<Uci>().unwrap().to_move(&pos).unwrap();
+    connection
+        .run_steps(&move_to_steps(&mv, pos.board(), &Graveyard::new()))
+        .await
+        .unwrap();
+    let pos = pos.play(&mv).unwrap();
+    drop(connection);
+
+    let report = server.join().unwrap().unwrap();
+    assert_eq!(report.occupancy, pos.board().occupied().0);
+    assert_eq!(report.led_frames, vec![colours]);
+    assert_eq!(report.magnet_moves.len(), 1);
+}
+
+#[tokio::test]
+async fn test_sensor_event_stream() {
+    let start = Chess::default().board().occupied();
+    let script = parse_script("lift e2\nplace e4\nlift d7 place d5").unwrap();
+    let mut pty = PtyBoard::open().unwrap();
+    let path = pty.path().unwrap();
+    let server = std::thread::spawn(move || {
+        let mut board = SimulatedBoard::new(Chess::default().board().occupied(), script);
+        pty.serve(&mut board).map(|()| board.report())
+    });
+
+    let mut connection = BoardConnection::open(_options(path)).await.unwrap();
+    assert!(connection.info().events);
+    let events = connection
+        .events()
+        .take(4)
+        .map(Result::unwrap)
+        .collect::<Vec<SwitchEvent>>()
+        .await;
+    let changes = events
+        .iter()
+        .map(|event| (event.square, event.change))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        changes,
+        [
+            (Square::E2, SwitchChange::Lifted),
+            (Square::E4, SwitchChange::Placed),
+            (Square::D7, SwitchChange::Lifted),
+            (Square::D5, SwitchChange::Placed),
+        ]
+    );
+    // each line is made after the one before it
+    assert!(events[0].timestamp < events[2].timestamp);
+
+    let occupancy = events
+        .iter()
+        .fold(start, |occupancy, event| event.apply(occupancy));
+    assert_eq!(connection.scan().await.unwrap(), occupancy);
+    drop(connection);
+
+    let report = server.join().unwrap().unwrap();
+    assert_eq!(report.occupancy, occupancy.0);
+}
@@ -0,0 +1,167 @@
+extern crate board_simulator;
+
+use board_simulator::board::{ACK, LED, MAGNET, SCAN, SENSOR};
+use board_simulator::{parse_script, SensorEvent, SimulatedBoard};
+use flagfall_core::{move_to_steps, Graveyard, Step};
+use shakmaty::{uci::Uci, Bitboard, Chess, Position, Square};
+
+#[allow(clippy::cast_possible_truncation)]
+fn _magnet_instruction(steps: &[Step]) -> Vec<u8> {
+    let mut instruction = vec![MAGNET];
+    for step in steps {
+        instruction.extend_from_slice(&(step.x as f32).to_le_bytes());
+        instruction.extend_from_slice(&(step.y as f32).to_le_bytes());
+        instruction.push(step.magnet.into());
+    }
+    instruction
+}
+
+fn _play(board: &mut SimulatedBoard, pos: &Chess, uci: &str) -> Chess {
+    let mv = uci.parse::<Uci>().unwrap().to_move(pos).unwrap();
+    let steps = move_to_steps(&mv, pos.board(), &Graveyard::new());
+    assert_eq!(board.handle(&_magnet_instruction(&steps)), Some(vec![ACK]));
+    pos.clone().play(&mv).unwrap()
+}
+
+#[test]
+fn test_script_parsing() {
+    let script = parse_script("# opening\nlift e2 place e4\n\nlift d7 # one at a time\n").unwrap();
+    assert_eq!(
+        script,
+        vec![
+            vec![
+                SensorEvent::Lift(Square::E2),
+                SensorEvent::Place(Square::E4)
+            ],
+            vec![SensorEvent::Lift(Square::D7)],
+        ]
+    );
+    assert_eq!(parse_script("lift e2\nplace").unwrap_err().line, 2);
+    assert!(parse_script("lift e9").is_err());
+    assert!(parse_script("drop e4").is_err());
+}
+
+#[test]
+fn test_sensor_reads_follow_the_script() {
+    let start = Chess::default().board().occupied();
+    let mut board = SimulatedBoard::new(start, parse_script("lift e2\nplace e4").unwrap());
+
+    let read = |reply: Option<Vec<u8>>| {
+        let reply = reply.unwrap();
+        assert_eq!(reply[0], SENSOR);
+        Bitboard(u64::from_le_bytes(reply[1..].try_into().unwrap()))
+    };
+    assert_eq!(read(board.handle(&[SCAN])), start);
+    assert_eq!(
+        read(board.handle(&[SENSOR])),
+        start ^ Bitboard::from_square(Square::E2)
+    );
+    assert_eq!(
+        read(board.handle(&[SENSOR])),
+        start ^ Bitboard::from_square(Square::E2) ^ Bitboard::from_square(Square::E4)
+    );
+    assert_eq!(board.remaining_reads(), 0);
+    assert_eq!(board.handle(&[SENSOR]), None);
+}
+
+#[test]
+fn test_magnet_steps_move_pieces() {
+    let mut board = SimulatedBoard::new(Chess::default().board().occupied(), Vec::new());
+    let pos = _play(&mut board, &Chess::default(), "e2e4");
+    let pos = _play(&mut board, &pos, "d7d5");
+    let pos = _play(&mut board, &pos, "g1f3");
+    assert_eq!(board.occupancy(), pos.board().occupied());
+
+    // the captured pawn is carried off the board and parked
+    let pos = _play(&mut board, &pos, "d5e4");
+    assert_eq!(board.occupancy(), pos.board().occupied());
+    let report = board.report();
+    assert_eq!(report.parked.len(), 1);
+    assert_eq!(report.magnet_moves.len(), 4);
+    assert!(report.collisions.is_empty(), "{:?}", report.collisions);
+}
+
+#[test]
+fn test_collisions_are_reported() {
+    let mut board = SimulatedBoard::new(Chess::default().board().occupied(), Vec::new());
+    // drag the a1 rook straight through the a2 pawn
+    let steps = [
+        Step {
+            x: 1.0,
+            y: 1.0,
+            magnet: false,
+        },
+        Step {
+            x: 1.0,
+            y: 4.0,
+            magnet: true,
+        },
+    ];
+    board.handle(&_magnet_instruction(&steps));
+    let report = board.report();
+    assert_eq!(report.collisions.len(), 1);
+    assert!(report.occupancy & Bitboard::from_square(Square::A4).0 != 0);
+}
+
+#[test]
+fn test_led_frames_are_recorded() {
+    let mut board = SimulatedBoard::new(Bitboard::EMPTY, Vec::new());
+    let mut instruction = vec![LED];
+    for square in 0..64_u32 {
+        let colour = if square == 0 { 0xFF_A500_u32 } else { 0 };
+        instruction.extend_from_slice(&colour.to_be_bytes()[1..]);
+    }
+    assert_eq!(board.handle(&instruction), Some(vec![ACK]));
+    let frames = board.report().led_frames;
+    assert_eq!(frames.len(), 1);
+    assert_eq!(frames[0].len(), 64);
+    assert_eq!(frames[0][0], 0xFF_A500);
+}
+
+#[cfg(unix)]
+#[test]
+fn test_serves_over_pty() {
+    use std::io::{Read, Write};
+    use std::time::Duration;
+
+    use board_simulator::pty::PtyBoard;
+    use serial_communicator::frame::{encode_frame, FrameDecoder};
+
+    let start = Chess::default().board().occupied();
+    let mut pty = PtyBoard::open().expect("[simulator_test::serves_over_pty] No pty");
+    let path = pty.path().unwrap();
+    let server = std::thread::spawn(move || {
+        let mut board = SimulatedBoard::new(start, parse_script("lift g1").unwrap());
+        pty.serve(&mut board).map(|()| board.report())
+    });
+
+    let mut port = serialport::new(path, 115_200)
+        .timeout(Duration::from_secs(5))
+        .open()
+        .expect("[simulator_test::serves_over_pty] Cannot open the pty");
+    // noise on the line is skipped
+    port.write_all(&[0x00, 0x13]).unwrap();
+    port.write_all(&encode_frame(SENSOR, &[])).unwrap();
+    let mut decoder = FrameDecoder::default();
+    let reply = loop {
+        if let Some(frame) = decoder.next_frame() {
+            break frame.unwrap();
+        }
+        let mut buf = [0_u8; 32];
+        let read = port.read(&mut buf).unwrap();
+        decoder.push(&buf[..read]);
+    };
+    assert_eq!(reply.opcode, SENSOR);
+    assert_eq!(
+        Bitboard(u64::from_le_bytes(reply.payload.try_into().unwrap())),
+        start ^ Bitboard::from_square(Square::G1)
+    );
+
+    // the script is done, so the next read ends the run
+    port.write_all(&encode_frame(SENSOR, &[])).unwrap();
+    let report = server.join().unwrap().unwrap();
+    assert_eq!(
+        report.occupancy,
+        (start ^ Bitboard::from_square(Square::G1)).0
+    );
+}
@@ -0,0 +1,136 @@
+//! The graveyards beside the board, where captured pieces are parked.
+//!
+//! Captured white pieces are parked along `x = 0.0` from the top down, and
+//! captured black pieces along `x = 9.0` from the bottom up, half a square
+//! apart. [`Graveyard`] keeps track of which piece is in which slot, so they
+//! can be fetched back for a promotion or to reset the board.
+
+use shakmaty::{Board, ByColor, Color, Move, Piece, Role, Square};
+
+use crate::path::Point;
+
+/// How many pieces fit in each graveyard, one more than the fifteen of a
+/// side that can be captured.
+pub const SLOTS: usize = 16;
+
+/// Which captured piece is parked in each slot of the two graveyards.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Graveyard {
+    slots: ByColor<[Option<Role>; SLOTS]>,
+}
+
+impl Graveyard {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Where `slot` of the graveyard for `color` is, in board coordinates.
+    #[must_use]
+    #[allow(clippy::cast_precision_loss)]
+    pub fn point(color: Color, slot: usize) -> Point {
+        let offset = slot as f64 / 2.0;
+        match color {
+            Color::White => (0.0, 8.5 - offset),
+            Color::Black => (9.0, 0.5 + offset),
+        }
+    }
+
+    /// The role of the piece in `slot` of the graveyard for `color`.
+    #[must_use]
+    pub fn get(&self, color: Color, slot: usize) -> Option<Role> {
+        self.slots.get(color).get(slot).copied().flatten()
+    }
+
+    /// How many pieces of `color` are parked.
+    #[must_use]
+    pub fn count(&self, color: Color) -> usize {
+        self.slots.get(color).iter().flatten().count()
+    }
+
+    /// The slot the next captured piece of `color` goes in, the first free
+    /// one, or `None` if the graveyard is full.
+    #[must_use]
+    pub fn next_slot(&self, color: Color) -> Option<usize> {
+        self.slots.get(color).iter().position(Option::is_none)
+    }
+
+    /// Parks `piece` in the next slot for its colour, returning the slot, or
+    /// `None` if the graveyard is full.
+    pub fn park(&mut self, piece: Piece) -> Option<usize> {
+        let slot = self.next_slot(piece.color)?;
+        self.slots.get_mut(piece.color)[slot] = Some(piece.role);
+        Some(slot)
+    }
+
+    /// Parks the piece `mv` captures on `board`, if any, returning the slot
+    /// it went in.
+    pub fn capture(&mut self, board: &Board, mv: &Move) -> Option<usize> {
+        let piece = board.piece_at(captured_square(mv)?)?;
+        self.park(piece)
+    }
+
+    /// Records what the magnet does to the graveyards playing `mv` on
+    /// `board` (see [`crate::motion::move_to_steps`]): the captured piece is
+    /// parked, and a promoting pawn is parked in exchange for a parked piece
+    /// of its new role, if there is one.
+    ///
+    /// Returns the role the pawn has to be swapped for by hand if there is
+    /// no such piece, or no room left for the pawn.
+    pub fn play(&mut self, board: &Board, mv: &Move) -> Option<Role> {
+        self.capture(board, mv);
+        let role = mv.promotion()?;
+        let color = board.color_at(mv.from()?)?;
+        let piece = Piece { color, role };
+        let pawn = Piece {
+            color,
+            role: Role::Pawn,
+        };
+        if self.find(piece).is_none() || self.park(pawn).is_none() {
+            return Some(role);
+        }
+        self.take(piece);
+        None
+    }
+
+    /// The slot holding a parked `piece`, the first if there are several.
+    #[must_use]
+    pub fn find(&self, piece: Piece) -> Option<usize> {
+        self.slots
+            .get(piece.color)
+            .iter()
+            .position(|&role| role == Some(piece.role))
+    }
+
+    /// Takes a parked `piece` out of the graveyard, returning the slot it was
+    /// in. The slot is reused by the next capture.
+    pub fn take(&mut self, piece: Piece) -> Option<usize> {
+        let slot = self.find(piece)?;
+        self.slots.get_mut(piece.color)[slot] = None;
+        Some(slot)
+    }
+
+    /// Every parked piece, with the slot it is in.
+    pub fn pieces(&self) -> impl Iterator<Item = (Piece, usize)> + '_ {
+        [Color::White, Color::Black]
+            .into_iter()
+            .flat_map(move |color| {
+                self.slots
+                    .get(color)
+                    .iter()
+                    .enumerate()
+                    .filter_map(move |(slot, &role)| Some((Piece { color, role: role? }, slot)))
+            })
+    }
+}
+
+/// The square of the piece `mv` captures, which is beside `mv.to()` for en
+/// passant.
+#[must_use]
+pub fn captured_square(mv: &Move) -> Option<Square> {
+    match *mv {
+        Move::EnPassant { from, to } => Some(Square::from_coords(to.file(), from.rank())),
+        _ if mv.is_capture() => Some(mv.to()),
+        _ => None,
+    }
+}
@@ -0,0 +1,48 @@
+//! Move inference from whole-board occupancy.
+//!
+//! Unlike the [`state`](crate::state) machine, which needs to see every
+//! square toggle in a sensible order, this looks only at the occupancy before
+//! and after a move. It is used to recover when the state machine gives up.
+
+use shakmaty::{Bitboard, Chess, Move, Position, Role};
+
+/// Lists the legal moves that turn the `last` stable occupancy into `current`.
+///
+/// `last` must be the occupancy of `position`; otherwise the diff cannot be
+/// interpreted and no moves are returned. Promotions to every role are listed
+/// separately, as they all leave the same occupancy.
+#[must_use]
+pub fn infer_moves(position: &Chess, last: Bitboard, current: Bitboard) -> Vec<Move> {
+    if last != position.board().occupied() {
+        return Vec::new();
+    }
+    position
+        .legal_moves()
+        .into_iter()
+        .filter(|mv| occupancy_after(position, mv) == current)
+        .collect()
+}
+
+/// Finds the one legal move that turns the `last` occupancy into `current`.
+///
+/// Returns `None` if no move or several moves fit the diff. Promotions that
+/// differ only by the promoted role count as a single move, and resolve to a
+/// queen.
+#[must_use]
+pub fn infer_move(position: &Chess, last: Bitboard, current: Bitboard) -> Option<Move> {
+    let mut candidates = infer_moves(position, last, current);
+    candidates.retain(|mv| matches!(mv.promotion(), None | Some(Role::Queen)));
+    if candidates.len() == 1 {
+        candidates.pop()
+    } else {
+        None
+    }
+}
+
+/// Computes the occupancy of `position` after playing `mv`.
+#[must_use]
+pub fn occupancy_after(position: &Chess, mv: &Move) -> Bitboard {
+    let mut after = position.clone();
+    after.play_unchecked(mv);
+    after.board().occupied()
+}
@@ -0,0 +1,298 @@
+//! LED hints for the board's 8x8 LED matrix.
+//!
+//! Each channel of an [`RGB`] is a [`Bitboard`] of the squares that have that
+//! channel switched on, so a square lit in both `r` and `g` shows orange.
+
+use shakmaty::{Bitboard, Chess, Move, Position, Rank, Role, Square};
+
+use crate::state::{
+    castling_targets, en_passant_attackers_of, en_passant_captures, en_passant_victims_of,
+    legal_castles, promotion_menu, State,
+};
+use crate::util::print_bitboard;
+
+/// One frame of the LED matrix, split into red, green and blue channels.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RGB {
+    pub r: Bitboard,
+    pub g: Bitboard,
+    pub b: Bitboard,
+}
+
+/// Computes the LED hints to show for `state`.
+///
+/// For a lifted friendly piece this lights up where it can go, for a lifted
+/// enemy piece the friendly pieces that can capture it, and for any invalid
+/// state the squares that need fixing.
+///
+/// # Panics
+///
+/// Panics if `state` is [`State::FriendlyPU`] on a square that is empty in
+/// `position`.
+#[allow(clippy::too_many_lines)]
+#[must_use]
+pub fn get_rgb(position: &Chess, state: State) -> RGB {
+    let color = position.turn();
+    let occupied = position.board().occupied();
+    let enemies = position.them();
+    match state {
+        State::Idle => RGB {
+            r: Bitboard::EMPTY,
+            g: Bitboard::EMPTY,
+            b: Bitboard::EMPTY,
+        },
+        State::FriendlyPU(square) => {
+            let mut canmv_to: Bitboard;
+            let mut is_promotion: bool = false;
+            if position.board().role_at(square).unwrap() == Role::Pawn {
+                let shift_direction = if color.is_white() { 1 } else { -1 };
+                canmv_to = Bitboard::from_square(square).shift(8 * shift_direction);
+                if (square.rank() == Rank::Second && color.is_white()
+                    || square.rank() == Rank::Seventh && color.is_black())
+                    && canmv_to.without(occupied).any()
+                {
+                    canmv_to =
+                        canmv_to.with(Bitboard::from_square(square).shift(16 * shift_direction));
+                }
+                canmv_to = canmv_to.without(occupied);
+                canmv_to = canmv_to.with(
+                    en_passant_captures(position)
+                        .into_iter()
+                        .filter(|(mv, _)| mv.from() == Some(square))
+                        .map(|(mv, _)| mv.to())
+                        .collect::<Bitboard>(),
+                );
+
+                if (square.rank() == Rank::Second && color.is_black()
+                    || square.rank() == Rank::Seventh && color.is_white())
+                    && canmv_to.without(occupied).any()
+                {
+                    is_promotion = true;
+                }
+            } else {
+                canmv_to = position.board().attacks_from(square).without(occupied);
+                // castling squares, which in Chess960 may hold the rook
+                canmv_to = canmv_to.with(
+                    legal_castles(position)
+                        .filter(|&(king, _)| king == square)
+                        .map(|(king, rook)| castling_targets(color, king, rook).0)
+                        .collect::<Bitboard>(),
+                );
+            }
+
+            let can_capture = position
+                .board()
+                .attacks_from(square)
+                .intersect(enemies)
+                .with(en_passant_victims_of(position, square));
+
+            if is_promotion {
+                let menu = get_promotion_menu_rgb(position, square, None);
+                RGB {
+                    r: canmv_to.with(can_capture).with(menu.r),
+                    g: can_capture.with(menu.g),
+                    b: canmv_to.with(menu.b),
+                }
+            } else {
+                RGB {
+                    r: can_capture,
+                    g: canmv_to.with(can_capture),
+                    b: Bitboard::from_square(square),
+                }
+            }
+        }
+        State::EnemyPU(square) => {
+            let attackers = position
+                .board()
+                .attacks_to(square, color, occupied)
+                .with(en_passant_attackers_of(position, square));
+            RGB {
+                r: Bitboard::EMPTY,
+                g: attackers,
+                b: Bitboard::from_square(square),
+            }
+        }
+        State::FriendlyAndEnemyPU(friendly_square, enemy_square) => {
+            // en passant lands beside the captured pawn rather than on it
+            let target = en_passant_captures(position)
+                .into_iter()
+                .find(|(mv, victim)| mv.from() == Some(friendly_square) && *victim == enemy_square)
+                .map_or(enemy_square, |(mv, _)| mv.to());
+            RGB {
+                r: Bitboard::EMPTY,
+                g: Bitboard::from_square(target),
+                b: Bitboard::from_square(friendly_square),
+            }
+        }
+        State::PromotionSelected(pawn_square, _, _) => {
+            get_promotion_menu_rgb(position, pawn_square, None)
+        }
+        State::PromotionPU(pawn_square, enemy_square, role) => {
+            let targets = position
+                .legal_moves()
+                .iter()
+                .filter(|mv| mv.from() == Some(pawn_square) && mv.promotion() == Some(role))
+                .map(Move::to)
+                .filter(|&to| enemy_square.is_none_or(|enemy| enemy == to))
+                .collect::<Bitboard>();
+            let captures = targets.intersect(enemies);
+            let menu = get_promotion_menu_rgb(position, pawn_square, Some(role));
+            RGB {
+                r: targets.with(menu.r),
+                g: captures.with(menu.g),
+                b: targets.without(captures).with(menu.b),
+            }
+        }
+        State::Castling(king_square, rook_square) => {
+            // either piece may land first
+            let (king_target, rook_target) = castling_targets(color, king_square, rook_square);
+            let targets = Bitboard::from_square(king_target) | Bitboard::from_square(rook_target);
+            RGB {
+                r: targets,
+                g: Bitboard::EMPTY,
+                b: targets,
+            }
+        }
+        State::CastlingPutRookDown(_, _, target_square)
+        | State::CastlingPutKingDown(_, _, target_square) => RGB {
+            r: Bitboard::from_square(target_square),
+            g: Bitboard::EMPTY,
+            b: Bitboard::from_square(target_square),
+        },
+        State::CastlingKingDown(king_square, rook_square) => {
+            // pick up the rook and carry it over the king
+            let (_, rook_target) = castling_targets(color, king_square, rook_square);
+            RGB {
+                r: Bitboard::from_square(rook_target),
+                g: Bitboard::EMPTY,
+                b: Bitboard::from_square(rook_target) | Bitboard::from_square(rook_square),
+            }
+        }
+        State::CastlingRookDown(king_square, rook_square) => {
+            let (king_target, _) = castling_targets(color, king_square, rook_square);
+            RGB {
+                r: Bitboard::from_square(king_target),
+                g: Bitboard::EMPTY,
+                b: Bitboard::from_square(king_target) | Bitboard::from_square(king_square),
+            }
+        }
+        State::InvalidPiecePU(_, square) | State::InvalidMove(_, square) => RGB {
+            r: Bitboard::from_square(square),
+            g: Bitboard::EMPTY,
+            b: Bitboard::EMPTY,
+        },
+        State::Error => RGB {
+            r: Bitboard::FULL,
+            g: Bitboard::EMPTY,
+            b: Bitboard::EMPTY,
+        },
+    }
+}
+
+/// Computes the LED hints that guide the board from `actual` back to the
+/// `expected` occupancy.
+///
+/// Squares missing a piece show red and squares holding an extra piece show
+/// blue. Matching squares stay dark.
+#[must_use]
+pub fn get_mismatch_rgb(expected: Bitboard, actual: Bitboard) -> RGB {
+    RGB {
+        r: expected.without(actual),
+        g: Bitboard::EMPTY,
+        b: actual.without(expected),
+    }
+}
+
+/// Lights the promotion menu for the pawn on `from`: white for a queen, red
+/// for a rook, blue for a bishop and green for a knight.
+///
+/// With `only` set, just the square of that role is lit.
+fn get_promotion_menu_rgb(position: &Chess, from: Square, only: Option<Role>) -> RGB {
+    let mut rgb = RGB {
+        r: Bitboard::EMPTY,
+        g: Bitboard::EMPTY,
+        b: Bitboard::EMPTY,
+    };
+    for (square, role) in promotion_menu(position, from) {
+        if only.is_some_and(|only| only != role) {
+            continue;
+        }
+        light_role(&mut rgb, square, role);
+    }
+    rgb
+}
+
+/// Lights `square` to ask for the pawn on it to be swapped for a piece of
+/// `role`, in that role's colour on the promotion menu.
+#[must_use]
+pub fn get_swap_rgb(square: Square, role: Role) -> RGB {
+    let mut rgb = RGB {
+        r: Bitboard::EMPTY,
+        g: Bitboard::EMPTY,
+        b: Bitboard::EMPTY,
+    };
+    light_role(&mut rgb, square, role);
+    rgb
+}
+
+/// Lights `square` in the colour of `role` on the promotion menu.
+fn light_role(rgb: &mut RGB, square: Square, role: Role) {
+    let (r, g, b) = match role {
+        Role::Rook => (true, false, false),
+        Role::Bishop => (false, false, true),
+        Role::Knight => (false, true, false),
+        _ => (true, true, true),
+    };
+    if r {
+        rgb.r.add(square);
+    }
+    if g {
+        rgb.g.add(square);
+    }
+    if b {
+        rgb.b.add(square);
+    }
+}
+
+/// Converts `rgb` into one 24-bit colour per square, starting from h8 and
+/// ending at a1.
+#[must_use]
+pub fn rgb_to_colours(rgb: RGB) -> Vec<u32> {
+    let rs = format!("{:064b}", rgb.r);
+    let gs = format!("{:064b}", rgb.g);
+    let bs = format!("{:064b}", rgb.b);
+    rs.chars()
+        .zip(gs.chars())
+        .zip(bs.chars())
+        .map(|((r, g), b)| match ((r, g), b) {
+            (('1', '0'), '0') => 0xFF_0000,
+            (('0', '1'), '0') => 0x00_8000,
+            (('0', '0'), '1') => 0x00_00FF,
+            (('1', '1'), '0') => 0xFF_A500,
+            (('1', '0'), '1') => 0x80_0080,
+            (('1', '1'), '1') => 0xFF_FFFF,
+            (('0', '1'), '1') => 0x40_E0D0,
+            (_, _) => 0x00_0000,
+        })
+        .collect()
+}
+
+/// Serialises `rgb` into a `WRITE LED` command, with the colours of
+/// [`rgb_to_colours`].
+#[must_use]
+pub fn rgb_to_str(rgb: RGB) -> String {
+    let mut output = String::from("WRITE LED");
+    for colour in rgb_to_colours(rgb) {
+        output.push(' ');
+        output.push_str(&colour.to_string());
+    }
+    output
+}
+
+/// Prints each channel of `rgb` to stderr as a board diagram.
+pub fn print_rgb(rgb: RGB) {
+    print_bitboard(rgb.r);
+    print_bitboard(rgb.g);
+    print_bitboard(rgb.b);
+}
@@ -0,0 +1,35 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+//! Board logic shared by the Flagfall programs.
+//!
+//! - [`state`] turns reed-switch square toggles into committed moves.
+//! - [`inference`] finds the move that explains an occupancy change.
+//! - [`led`] produces the LED hints shown for each sensing state.
+//! - [`motion`] converts opponent moves into `CoreXY` magnet steps.
+//! - [`path`] routes carried pieces between the others.
+//! - [`graveyard`] keeps track of the captured pieces parked off the board.
+//! - [`reset`] plans putting every piece back for a new game.
+//! - [`util`] holds square numbering and debug printing helpers.
+//! - [`variant`] builds starting positions, including Chess960.
+
+pub mod graveyard;
+pub mod inference;
+pub mod led;
+pub mod motion;
+pub mod path;
+pub mod reset;
+pub mod state;
+pub mod util;
+pub mod variant;
+
+pub use graveyard::Graveyard;
+pub use led::{get_mismatch_rgb, get_rgb, get_swap_rgb, rgb_to_colours, rgb_to_str, RGB};
+pub use motion::{
+    capture_piece, carry, check_steps, move_to_steps, split_steps, standing_pieces, steps_to_str,
+    Step, StepError,
+};
+pub use reset::{plan_reset, ResetPlan};
+pub use state::{
+    castling_targets, get_square_changes, promotion_menu, update_state, update_state_batch,
+    SquareChange, State,
+};
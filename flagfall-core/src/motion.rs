@@ -0,0 +1,333 @@
+//! Motion planning for the electromagnet on the `CoreXY` gantry.
+//!
+//! Board coordinates are in squares: a1 is at `(1.0, 1.0)` and h8 at
+//! `(8.0, 8.0)`. Captured pieces are parked in the graveyards off either side
+//! (see [`crate::graveyard`]).
+
+use std::fmt::Display;
+
+use log::warn;
+use shakmaty::{Board, Color, Move, Piece, Square};
+
+use crate::graveyard::{captured_square, Graveyard};
+use crate::path::{plan_path, plan_through, Obstacles, Point};
+use crate::state::castling_targets;
+use crate::util::{file_to_float, rank_to_float};
+
+/// A single waypoint for the magnet carriage.
+#[derive(Debug, Clone, Copy)]
+pub struct Step {
+    pub x: f64,
+    pub y: f64,
+    /// Whether the magnet is engaged while travelling to this waypoint.
+    pub magnet: bool,
+}
+
+/// Serialises `steps` into a `WRITE MAGNET` command for the serial-communicator.
+#[must_use]
+pub fn steps_to_str(steps: &[Step]) -> String {
+    let mut output = String::from("WRITE MAGNET");
+    for step in steps {
+        let x = step.x.to_string();
+        let y = step.y.to_string();
+        let magnet = step.magnet.to_string();
+        output = format!("{output} {x} {y} {magnet}");
+    }
+    output
+}
+
+/// Splits `steps` into instructions of at most `max` steps.
+///
+/// The firmware lets go of the piece at the end of an instruction, so they are cut where the
+/// magnet is off if they can be; a carry too long for one instruction is cut
+/// at a waypoint instead, and the next instruction picks the piece up again
+/// where it was left.
+///
+/// # Panics
+///
+/// Panics if `max` is zero.
+#[must_use]
+pub fn split_steps(steps: &[Step], max: usize) -> Vec<&[Step]> {
+    assert!(max > 0, "[motion::split_steps] No room for a step");
+    let mut instructions = Vec::new();
+    let mut rest = steps;
+    while rest.len() > max {
+        let cut = (1..=max).rev().find(|&i| !rest[i].magnet).unwrap_or(max);
+        let (instruction, tail) = rest.split_at(cut);
+        instructions.push(instruction);
+        rest = tail;
+    }
+    if !rest.is_empty() {
+        instructions.push(rest);
+    }
+    instructions
+}
+
+/// Plans the magnet steps that play `mv` on the physical board, routing each
+/// carried piece around the others (see [`crate::path`]).
+///
+/// `board` is the position before the move. A captured piece is parked in
+/// the next slot of `graveyard`, and a promoting pawn is swapped for a parked
+/// piece of its new role if there is one, all left for the caller to record
+/// with [`Graveyard::play`] once the move is made.
+///
+/// # Panics
+///
+/// Panics if `mv` is a drop, which has no origin square, if `board` has no
+/// piece on its origin square, or if the captured piece's graveyard is full.
+#[must_use]
+pub fn move_to_steps(mv: &Move, board: &Board, graveyard: &Graveyard) -> Vec<Step> {
+    let mut steps = Vec::new();
+    let mut obstacles = standing_pieces(board, graveyard);
+    let from = mv.from().unwrap();
+    let current_color = board.color_at(from).unwrap();
+
+    if let Move::Castle { king, rook } = *mv {
+        //the rook waits in the lane off the board edge while the king slides
+        //along the back rank, so this works for any Chess960 setup too
+        let (king_to, rook_to) = castling_targets(current_color, king, rook);
+        let (rook_x, rook_y) = centre(rook);
+        let lane = if current_color == Color::White {
+            (rook_x, rook_y - 0.5)
+        } else {
+            (rook_x, rook_y + 0.5)
+        };
+        steps.extend(carry(&mut obstacles, centre(rook), lane));
+        if king_to != king {
+            steps.extend(carry(&mut obstacles, centre(king), centre(king_to)));
+        }
+        steps.extend(carry(&mut obstacles, lane, centre(rook_to)));
+        return steps;
+    }
+
+    if let Some(captured) = captured_square(mv) {
+        let parked = parking_spot(graveyard, !current_color);
+        steps.extend(carry(&mut obstacles, centre(captured), parked));
+    }
+
+    if let Some(role) = mv.promotion() {
+        // the pawn is put away and the new piece fetched in its place, unless
+        // there is none to fetch, when the pawn is swapped by hand
+        let piece = Piece {
+            color: current_color,
+            role,
+        };
+        if let (Some(slot), Some(pawn_slot)) =
+            (graveyard.find(piece), graveyard.next_slot(current_color))
+        {
+            let pawn_spot = Graveyard::point(current_color, pawn_slot);
+            steps.extend(carry(&mut obstacles, centre(from), pawn_spot));
+            let parked = Graveyard::point(current_color, slot);
+            steps.extend(carry(&mut obstacles, parked, centre(mv.to())));
+            return steps;
+        }
+    }
+
+    steps.extend(carry(&mut obstacles, centre(from), centre(mv.to())));
+    steps
+}
+
+/// Plans the magnet steps that carry the piece on `square` of `board` off to
+/// the next slot of its colour's graveyard.
+///
+/// # Panics
+///
+/// Panics if `board` has no piece on `square`, or if its graveyard is full.
+#[must_use]
+pub fn capture_piece(board: &Board, square: Square, graveyard: &Graveyard) -> Vec<Step> {
+    let mut obstacles = standing_pieces(board, graveyard);
+    let parked = parking_spot(graveyard, board.color_at(square).unwrap());
+    carry(&mut obstacles, centre(square), parked)
+}
+
+/// The centre of `square` in board coordinates.
+pub(crate) fn centre(square: Square) -> Point {
+    (file_to_float(square.file()), rank_to_float(square.rank()))
+}
+
+/// Where the next captured piece of `color` is parked.
+fn parking_spot(graveyard: &Graveyard, color: Color) -> Point {
+    let slot = graveyard
+        .next_slot(color)
+        .expect("[motion::parking_spot] Graveyard is full");
+    Graveyard::point(color, slot)
+}
+
+/// Where the pieces stand: on `board`, and parked in `graveyard`.
+#[must_use]
+pub fn standing_pieces(board: &Board, graveyard: &Graveyard) -> Obstacles {
+    let mut obstacles = Obstacles::new();
+    for square in board.occupied() {
+        obstacles.add(centre(square));
+    }
+    for (piece, slot) in graveyard.pieces() {
+        obstacles.add(Graveyard::point(piece.color, slot));
+    }
+    obstacles
+}
+
+/// Plans the steps that pick up the piece at `from` and carry it to `to`
+/// around the pieces standing at `obstacles`, and moves it there in
+/// `obstacles`.
+///
+/// If there is no way around, the pieces in the way are carried aside first
+/// and put back once it has passed.
+pub fn carry(obstacles: &mut Obstacles, from: Point, to: Point) -> Vec<Step> {
+    let mut steps = Vec::new();
+    obstacles.remove(from);
+    if plan_path(from, to, obstacles).is_some() {
+        drag(&mut steps, obstacles, from, to);
+        return steps;
+    }
+
+    obstacles.add(from);
+    let moved = clear_way(&mut steps, obstacles, from, to);
+    obstacles.remove(from);
+    drag(&mut steps, obstacles, from, to);
+    // put back in the reverse order, as the last moved may be in the way of
+    // the others
+    for &(home, spot) in moved.iter().rev() {
+        obstacles.remove(spot);
+        drag(&mut steps, obstacles, spot, home);
+    }
+    steps
+}
+
+/// Carries each piece on the cheapest route from `from` to `to` to a free
+/// spot off it, returning where each one came from and went.
+fn clear_way(
+    steps: &mut Vec<Step>,
+    obstacles: &mut Obstacles,
+    from: Point,
+    to: Point,
+) -> Vec<(Point, Point)> {
+    let Some(route) = plan_through(from, to, obstacles) else {
+        return Vec::new();
+    };
+    let near_route = |spot: Point| {
+        std::iter::once(&from)
+            .chain(&route)
+            .any(|&point| (spot.0 - point.0).abs() <= 0.5 && (spot.1 - point.1).abs() <= 0.5)
+    };
+    let mut moved = Vec::new();
+    for &blocker in &route {
+        if blocker == to || !obstacles.contains(blocker) {
+            continue;
+        }
+        obstacles.remove(blocker);
+        let spot = obstacles
+            .free_near(blocker)
+            .into_iter()
+            .filter(|&spot| !near_route(spot))
+            .find(|&spot| plan_path(blocker, spot, obstacles).is_some());
+        if let Some(spot) = spot {
+            drag(steps, obstacles, blocker, spot);
+            moved.push((blocker, spot));
+        } else {
+            warn!("nowhere to move {blocker:?} out of the way");
+            obstacles.add(blocker);
+        }
+    }
+    moved
+}
+
+/// Adds the steps that carry the piece at `from` to `to` along a planned
+/// route, which is assumed to be clear, or straight if there is none.
+fn drag(steps: &mut Vec<Step>, obstacles: &mut Obstacles, from: Point, to: Point) {
+    steps.push(Step {
+        x: from.0,
+        y: from.1,
+        magnet: false,
+    });
+    let route = plan_path(from, to, obstacles).unwrap_or_else(|| {
+        warn!("no clear route from {from:?} to {to:?}, going straight");
+        vec![to]
+    });
+    steps.extend(route.into_iter().map(|(x, y)| Step { x, y, magnet: true }));
+    obstacles.add(to);
+}
+
+/// Why a plan of steps cannot be carried out safely.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StepError {
+    /// The magnet engages where there is no piece to carry.
+    NothingToCarry(Point),
+    /// A carried piece passes too close to a standing one on the leg ending
+    /// at this point.
+    Collision(Point),
+    /// A carried piece is taken onto another.
+    Occupied(Point),
+}
+
+impl Display for StepError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NothingToCarry(at) => write!(f, "no piece to pick up at {at:?}"),
+            Self::Collision(at) => {
+                write!(f, "carried piece knocks into another on the way to {at:?}")
+            }
+            Self::Occupied(at) => write!(f, "carried piece taken onto another at {at:?}"),
+        }
+    }
+}
+
+impl std::error::Error for StepError {}
+
+/// Checks that `steps` can be carried out on the pieces standing at
+/// `obstacles`, as the firmware would, and returns where the pieces stand
+/// afterwards.
+///
+/// The carriage starts wherever the first step is, and the magnet engages
+/// on the way to a step with `magnet` set, picking up the piece under the
+/// carriage, and releases on the way to one without.
+///
+/// # Errors
+///
+/// Returns the first [`StepError`] in `steps`.
+pub fn check_steps(steps: &[Step], obstacles: &Obstacles) -> Result<Obstacles, StepError> {
+    let mut obstacles = obstacles.clone();
+    let Some(first) = steps.first() else {
+        return Ok(obstacles);
+    };
+    let mut carriage = (first.x, first.y);
+    let mut carrying = false;
+    for step in steps {
+        if step.magnet && !carrying {
+            if !obstacles.contains(carriage) {
+                return Err(StepError::NothingToCarry(carriage));
+            }
+            obstacles.remove(carriage);
+            carrying = true;
+        } else if !step.magnet && carrying {
+            put_down(&mut obstacles, carriage)?;
+            carrying = false;
+        }
+        let target = (step.x, step.y);
+        if carrying && obstacles.contains(target) {
+            return Err(StepError::Occupied(target));
+        }
+        if carrying && !obstacles.is_clear(carriage, target) {
+            return Err(StepError::Collision(target));
+        }
+        carriage = target;
+    }
+    if carrying {
+        put_down(&mut obstacles, carriage)?;
+    }
+    Ok(obstacles)
+}
+
+fn put_down(obstacles: &mut Obstacles, at: Point) -> Result<(), StepError> {
+    if obstacles.contains(at) {
+        return Err(StepError::Occupied(at));
+    }
+    obstacles.add(at);
+    Ok(())
+}
+
+/// Prints the fields of `step` to stdout.
+pub fn print_step(step: Step) {
+    println!("x: {}", step.x);
+    println!("y: {}", step.y);
+    println!("magnet: {}", step.magnet);
+}
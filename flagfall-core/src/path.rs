@@ -0,0 +1,262 @@
+//! Routing a carried piece around the others.
+//!
+//! A piece dragged by the magnet knocks over anything it passes too close to,
+//! so it is routed over a grid of points half a square apart: the centres of
+//! squares, and the lanes between them where a piece passes half a square
+//! from its neighbours. The route is the cheapest one by A*, counting each
+//! turn as a little extra as the carriage stops there.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap, HashSet};
+use std::ops::RangeInclusive;
+
+/// A point on or around the board, in squares as for [`crate::Step`].
+pub type Point = (f64, f64);
+
+/// A grid point, in half squares.
+type Node = (i32, i32);
+
+/// Where the carriage can go, in half squares: from the white graveyard at
+/// `x = 0.0` to the black one at `x = 9.0`, and from the lane below the first
+/// rank to the lane above the eighth.
+const X_RANGE: RangeInclusive<i32> = 0..=18;
+const Y_RANGE: RangeInclusive<i32> = 1..=17;
+
+/// The cost of moving half a square straight, or diagonally.
+const STRAIGHT_COST: u32 = 10;
+const DIAGONAL_COST: u32 = 14;
+/// The cost of a stop to change direction.
+const TURN_COST: u32 = 6;
+/// The cost of passing over a piece that has to be moved out of the way and
+/// back, worth a detour of several squares.
+const BLOCKER_COST: u32 = 200;
+
+/// How close a carried piece may pass to a standing piece's centre before
+/// they knock together.
+pub const CLEARANCE: f64 = 0.35;
+
+const DIRECTIONS: [Node; 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+/// The direction of the start of a route, before any move.
+const NO_DIRECTION: usize = DIRECTIONS.len();
+
+/// Where pieces are standing, on or off the board.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Obstacles {
+    nodes: HashSet<Node>,
+}
+
+impl Obstacles {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Marks a piece standing at `at`, rounded to the nearest half square.
+    pub fn add(&mut self, at: Point) {
+        self.nodes.insert(node(at));
+    }
+
+    pub fn remove(&mut self, at: Point) {
+        self.nodes.remove(&node(at));
+    }
+
+    #[must_use]
+    pub fn contains(&self, at: Point) -> bool {
+        self.nodes.contains(&node(at))
+    }
+
+    /// Whether a piece carried straight from `from` to `to` keeps
+    /// [`CLEARANCE`] from every obstacle.
+    #[must_use]
+    pub fn is_clear(&self, from: Point, to: Point) -> bool {
+        self.nodes
+            .iter()
+            .all(|&obstacle| distance_to_leg(point(obstacle), from, to) >= CLEARANCE - f64::EPSILON)
+    }
+
+    /// The free points within the carriage's reach, nearest to `at` first,
+    /// for parking a piece out of the way.
+    #[must_use]
+    pub fn free_near(&self, at: Point) -> Vec<Point> {
+        let at = node(at);
+        let mut free: Vec<Node> = X_RANGE
+            .flat_map(|x| Y_RANGE.map(move |y| (x, y)))
+            .filter(|&spot| spot != at && !self.blocks(spot))
+            .collect();
+        free.sort_by_key(|&spot| estimate(at, spot));
+        free.into_iter().map(point).collect()
+    }
+
+    fn blocks(&self, node: Node) -> bool {
+        self.nodes.contains(&node)
+    }
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn node(at: Point) -> Node {
+    ((at.0 * 2.0).round() as i32, (at.1 * 2.0).round() as i32)
+}
+
+fn point(node: Node) -> Point {
+    (f64::from(node.0) / 2.0, f64::from(node.1) / 2.0)
+}
+
+/// How far `at` is from the straight leg between `from` and `to`.
+fn distance_to_leg(at: Point, from: Point, to: Point) -> f64 {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let length = dx.mul_add(dx, dy * dy);
+    let t = if length == 0.0 {
+        0.0
+    } else {
+        ((at.0 - from.0).mul_add(dx, (at.1 - from.1) * dy) / length).clamp(0.0, 1.0)
+    };
+    (at.0 - dx.mul_add(t, from.0)).hypot(at.1 - dy.mul_add(t, from.1))
+}
+
+/// The cheapest possible cost from `from` to `to`, ignoring obstacles and
+/// turns.
+fn estimate(from: Node, to: Node) -> u32 {
+    let dx = from.0.abs_diff(to.0);
+    let dy = from.1.abs_diff(to.1);
+    STRAIGHT_COST * (dx.max(dy) - dx.min(dy)) + DIAGONAL_COST * dx.min(dy)
+}
+
+/// Whether a piece can be carried from `node` one step in `direction`
+/// without touching an obstacle, other than one on `goal`, or one on the next
+/// node if `through` is set.
+fn can_step(obstacles: &Obstacles, node: Node, direction: Node, goal: Node, through: bool) -> bool {
+    let next = (node.0 + direction.0, node.1 + direction.1);
+    if !X_RANGE.contains(&next.0) || !Y_RANGE.contains(&next.1) {
+        return false;
+    }
+    if next != goal && !through && obstacles.blocks(next) {
+        return false;
+    }
+    if direction.0 == 0 || direction.1 == 0 {
+        return true;
+    }
+    // a diagonal along a lane would clip the pieces on either side of it, so
+    // only go between a square's centre and its corners, and not past a piece
+    // parked next to the corner
+    (node.0 + node.1) % 2 == 0
+        && !obstacles.blocks((next.0, node.1))
+        && !obstacles.blocks((node.0, next.1))
+}
+
+/// Plans a route for a piece carried from `from` to `to` that keeps clear of
+/// `obstacles`, as the points to stop at after `from`.
+///
+/// Each leg is straight or diagonal, which is how the carriage moves between
+/// two points anyway.
+///
+/// Returns `None` if either end is out of the carriage's reach or there is
+/// no way through.
+#[must_use]
+pub fn plan_path(from: Point, to: Point, obstacles: &Obstacles) -> Option<Vec<Point>> {
+    search(node(from), node(to), obstacles, None).map(|nodes| waypoints(&nodes))
+}
+
+/// Plans the route from `from` to `to` that would be cheapest if some of
+/// `obstacles` were moved out of the way, as every point half a square apart
+/// along it after `from`.
+///
+/// The obstacles it passes over are the ones to move. Returns `None` if
+/// either end is out of the carriage's reach.
+#[must_use]
+pub fn plan_through(from: Point, to: Point, obstacles: &Obstacles) -> Option<Vec<Point>> {
+    let nodes = search(node(from), node(to), obstacles, Some(BLOCKER_COST))?;
+    Some(nodes.into_iter().skip(1).map(point).collect())
+}
+
+/// A* from `start` to `goal`, returning every node along the way. Obstacles
+/// may be passed over at `blocker_cost` each if it is given.
+fn search(
+    start: Node,
+    goal: Node,
+    obstacles: &Obstacles,
+    blocker_cost: Option<u32>,
+) -> Option<Vec<Node>> {
+    for end in [start, goal] {
+        if !X_RANGE.contains(&end.0) || !Y_RANGE.contains(&end.1) {
+            return None;
+        }
+    }
+
+    // searched by node and the direction it was reached in, as turns cost
+    let mut costs: HashMap<(Node, usize), u32> = HashMap::new();
+    let mut came_from: HashMap<(Node, usize), (Node, usize)> = HashMap::new();
+    let mut open = BinaryHeap::new();
+    costs.insert((start, NO_DIRECTION), 0);
+    open.push(Reverse((estimate(start, goal), 0, start, NO_DIRECTION)));
+
+    while let Some(Reverse((_, cost, node, direction))) = open.pop() {
+        if node == goal {
+            let mut nodes = vec![node];
+            let mut current = (node, direction);
+            while let Some(&previous) = came_from.get(&current) {
+                nodes.push(previous.0);
+                current = previous;
+            }
+            nodes.reverse();
+            return Some(nodes);
+        }
+        if costs
+            .get(&(node, direction))
+            .is_some_and(|&best| best < cost)
+        {
+            continue;
+        }
+        for (next_direction, &step) in DIRECTIONS.iter().enumerate() {
+            if !can_step(obstacles, node, step, goal, blocker_cost.is_some()) {
+                continue;
+            }
+            let next = (node.0 + step.0, node.1 + step.1);
+            let mut next_cost = cost
+                + if step.0 == 0 || step.1 == 0 {
+                    STRAIGHT_COST
+                } else {
+                    DIAGONAL_COST
+                };
+            if direction != NO_DIRECTION && direction != next_direction {
+                next_cost += TURN_COST;
+            }
+            if next != goal && obstacles.blocks(next) {
+                next_cost += blocker_cost.unwrap_or_default();
+            }
+            let key = (next, next_direction);
+            if costs.get(&key).is_some_and(|&best| best <= next_cost) {
+                continue;
+            }
+            costs.insert(key, next_cost);
+            came_from.insert(key, (node, direction));
+            open.push(Reverse((
+                next_cost + estimate(next, goal),
+                next_cost,
+                next,
+                next_direction,
+            )));
+        }
+    }
+    None
+}
+
+/// The points where the route through `nodes` turns, and its last point.
+fn waypoints(nodes: &[Node]) -> Vec<Point> {
+    let direction = |pair: &[Node]| (pair[1].0 - pair[0].0, pair[1].1 - pair[0].1);
+    let mut points: Vec<Point> = nodes
+        .windows(3)
+        .filter(|legs| direction(&legs[..2]) != direction(&legs[1..]))
+        .map(|legs| point(legs[1]))
+        .collect();
+    points.extend(nodes.last().map(|&end| point(end)));
+    points
+}
@@ -0,0 +1,131 @@
+//! Setting the pieces back up for a new game.
+//!
+//! Every piece that is not on a square of the target position where it
+//! belongs is a candidate, whether it stands on the board or is parked in
+//! a graveyard. The nearest candidate that can go straight to an empty
+//! square waiting for its kind of piece goes first. When none can, a
+//! candidate standing in the way is parked in its graveyard to make room.
+
+use shakmaty::{Board, Piece, Square};
+
+use crate::graveyard::Graveyard;
+use crate::motion::{carry, centre, standing_pieces, Step};
+use crate::path::Point;
+
+/// How to set the pieces up in a target position.
+#[derive(Debug, Clone)]
+pub struct ResetPlan {
+    /// The magnet steps, one piece after another.
+    pub steps: Vec<Step>,
+    /// The graveyards once the steps are done, holding any pieces the target
+    /// position has no room for.
+    pub graveyard: Graveyard,
+    /// The squares of the target position left without their piece, as none
+    /// was to be found, to be filled by hand.
+    pub missing: Vec<Square>,
+}
+
+/// Where a piece to be put back is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    Board(Square),
+    Graveyard(usize),
+}
+
+/// Plans the magnet steps that take the pieces from `board` and `graveyard`
+/// to where they stand in `target`, such as the starting position.
+#[must_use]
+pub fn plan_reset(board: &Board, graveyard: &Graveyard, target: &Board) -> ResetPlan {
+    let mut obstacles = standing_pieces(board, graveyard);
+    let mut board = board.clone();
+    let mut graveyard = graveyard.clone();
+    let mut steps = Vec::new();
+
+    loop {
+        let unsettled: Vec<(Source, Piece)> = board
+            .occupied()
+            .into_iter()
+            .filter_map(|square| Some((square, board.piece_at(square)?)))
+            .filter(|&(square, piece)| target.piece_at(square) != Some(piece))
+            .map(|(square, piece)| (Source::Board(square), piece))
+            // parked pieces are taken back in the order they are found
+            .chain(
+                graveyard
+                    .pieces()
+                    .filter(|&(piece, slot)| graveyard.find(piece) == Some(slot))
+                    .map(|(piece, slot)| (Source::Graveyard(slot), piece)),
+            )
+            .collect();
+
+        let nearest = unsettled
+            .iter()
+            .flat_map(|&(source, piece)| {
+                target
+                    .occupied()
+                    .without(board.occupied())
+                    .into_iter()
+                    .filter(move |&square| target.piece_at(square) == Some(piece))
+                    .map(move |square| (source, piece, square))
+            })
+            .min_by(|a, b| {
+                let length = |&(source, piece, square): &(Source, Piece, Square)| {
+                    distance(point(source, piece), centre(square))
+                };
+                length(a).total_cmp(&length(b))
+            });
+        if let Some((source, piece, square)) = nearest {
+            steps.extend(carry(&mut obstacles, point(source, piece), centre(square)));
+            match source {
+                Source::Board(from) => {
+                    board.discard_piece_at(from);
+                }
+                Source::Graveyard(_) => {
+                    graveyard.take(piece);
+                }
+            }
+            board.set_piece_at(square, piece);
+            continue;
+        }
+
+        // park a piece that is in the way, or that has no place to go at all
+        let in_the_way = unsettled
+            .iter()
+            .filter_map(|&(source, piece)| match source {
+                Source::Board(square) => Some((square, piece)),
+                Source::Graveyard(_) => None,
+            })
+            .min_by_key(|&(square, _)| target.piece_at(square).is_none());
+        let Some((square, piece)) = in_the_way else {
+            break;
+        };
+        let Some(slot) = graveyard.park(piece) else {
+            break;
+        };
+        let parked = Graveyard::point(piece.color, slot);
+        steps.extend(carry(&mut obstacles, centre(square), parked));
+        board.discard_piece_at(square);
+    }
+
+    let missing = target
+        .occupied()
+        .into_iter()
+        .filter(|&square| board.piece_at(square) != target.piece_at(square))
+        .collect();
+    ResetPlan {
+        steps,
+        graveyard,
+        missing,
+    }
+}
+
+/// Where the piece at `source` is.
+fn point(source: Source, piece: Piece) -> Point {
+    match source {
+        Source::Board(square) => centre(square),
+        Source::Graveyard(slot) => Graveyard::point(piece.color, slot),
+    }
+}
+
+fn distance(a: Point, b: Point) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
@@ -0,0 +1,621 @@
+//! The reed-switch sensing state machine.
+//!
+//! Every time a reed switch toggles, the square it belongs to is fed into
+//! [`update_state`] together with the current [`State`]. Once a full move has
+//! been observed, the move is returned and the machine goes back to
+//! [`State::Idle`].
+//!
+//! A single sensor poll can report several toggled squares at once, so
+//! [`update_state_batch`] resolves a whole poll's worth of [`SquareChange`]s.
+
+use log::{info, warn};
+use shakmaty::{Bitboard, CastlingSide, Chess, Color, Move, Position, Rank, Role, Square};
+
+/// Where the player currently is in the process of making a move.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum State {
+    /// No pieces are lifted.
+    Idle,
+    /// A friendly piece has been lifted from the square.
+    FriendlyPU(Square),
+    /// An enemy piece has been lifted from the square.
+    EnemyPU(Square),
+    /// A friendly and an enemy piece are both lifted, in that order of fields.
+    FriendlyAndEnemyPU(Square, Square),
+    /// The king and rook (in that order) are both lifted for castling.
+    Castling(Square, Square),
+    /// The king has been placed; waiting for the rook to land on the last square.
+    CastlingPutRookDown(Square, Square, Square),
+    /// The rook has been placed; waiting for the king to land on the last square.
+    CastlingPutKingDown(Square, Square, Square),
+    /// The king (first field) was moved onto its castling square before the
+    /// rook (second field) was touched. The rook still has to follow.
+    CastlingKingDown(Square, Square),
+    /// The rook (second field) was moved onto its castling square before the
+    /// king (first field) was touched. The king still has to follow, unless
+    /// another piece is touched and the rook moved on its own.
+    CastlingRookDown(Square, Square),
+    /// A promoting pawn, lifted from the first square, has been put down on
+    /// the menu square (third field) of the role it should promote to. The
+    /// second field is the enemy piece already lifted for a capture, if any.
+    PromotionSelected(Square, Option<Square>, Square),
+    /// A promoting pawn has been lifted from the first square with a role
+    /// chosen from the promotion menu. The second field is the enemy piece
+    /// lifted for a capture, if any.
+    PromotionPU(Square, Option<Square>, Role),
+    /// A piece was lifted that cannot be moved, optionally on top of an
+    /// earlier lifted piece. It must be put back.
+    InvalidPiecePU(Option<Square>, Square),
+    /// A piece was lifted from the first square and put down on the second
+    /// square, which is not a legal move.
+    InvalidMove(Square, Square),
+    /// The sensor readings no longer make sense. The machine stays here until
+    /// the board is restored to the position, see [`get_mismatch_rgb`](crate::led::get_mismatch_rgb).
+    Error,
+}
+
+/// Advances the state machine by one toggled square.
+///
+/// `instruction` is the number of the square whose reed switch changed, from
+/// `0` (a1) to `63` (h8). Returns the next state, and the move that was
+/// completed by this toggle, if any.
+///
+/// # Panics
+///
+/// Panics if `state` refers to a lifted piece that is not present on the
+/// board of `position`.
+#[allow(clippy::too_many_lines, clippy::cognitive_complexity)]
+#[must_use]
+pub fn update_state(position: &Chess, instruction: u32, state: State) -> (State, Option<Move>) {
+    let color = position.turn();
+    let square = Square::new(instruction);
+    let occupied = position.board().occupied();
+    let friendlies = position.us();
+    let enemies = position.them();
+
+    match state {
+        State::Idle => {
+            if friendlies.contains(square) {
+                (State::FriendlyPU(square), None)
+            } else if enemies.contains(square) {
+                if position.board().attacks_to(square, color, occupied).any()
+                    || en_passant_captures(position)
+                        .iter()
+                        .any(|&(_, victim)| victim == square)
+                {
+                    (State::EnemyPU(square), None)
+                } else {
+                    (State::InvalidPiecePU(None, square), None)
+                }
+            } else {
+                (State::Error, None)
+            }
+        }
+        State::FriendlyPU(prev_square) => {
+            let role_picked_up = position.board().role_at(prev_square).unwrap();
+            let can_capture = position
+                .board()
+                .attacks_from(prev_square)
+                .intersect(enemies)
+                .with(en_passant_victims_of(position, prev_square));
+            if prev_square == square {
+                (State::Idle, None)
+            } else if role_picked_up == Role::Rook
+                && position.board().role_at(square) == Some(Role::King)
+            {
+                //castling
+                let mv = Move::Castle {
+                    king: square,
+                    rook: prev_square,
+                };
+                if position.is_legal(&mv) {
+                    (State::Castling(square, prev_square), None)
+                } else {
+                    (State::InvalidPiecePU(Some(prev_square), square), None)
+                }
+            } else if role_picked_up == Role::King
+                && position.board().role_at(square) == Some(Role::Rook)
+            {
+                //castling
+                let mv = Move::Castle {
+                    king: prev_square,
+                    rook: square,
+                };
+                if position.is_legal(&mv) {
+                    (State::Castling(prev_square, square), None)
+                } else {
+                    (State::InvalidPiecePU(Some(prev_square), square), None)
+                }
+            } else if friendlies.contains(square)
+                || (enemies.contains(square) && !can_capture.contains(square))
+            {
+                (State::InvalidPiecePU(Some(prev_square), square), None)
+            } else if can_capture.contains(square) {
+                (State::FriendlyAndEnemyPU(prev_square, square), None)
+            } else if role_picked_up == Role::Pawn
+                && (square.rank() == Rank::First || square.rank() == Rank::Eighth)
+            {
+                //promotions, straight onto the last rank means a queen
+                let mv = Move::Normal {
+                    role: (Role::Pawn),
+                    from: (prev_square),
+                    capture: (None),
+                    to: (square),
+                    promotion: (Some(Role::Queen)),
+                };
+                if position.is_legal(&mv) {
+                    info!("PROMOTED");
+                    (State::Idle, Some(mv))
+                } else {
+                    (State::InvalidMove(prev_square, square), None)
+                }
+            } else if menu_role(position, prev_square, square).is_some() {
+                (State::PromotionSelected(prev_square, None, square), None)
+            } else {
+                let mv = Move::Normal {
+                    role: (role_picked_up),
+                    from: (prev_square),
+                    capture: (None),
+                    to: (square),
+                    promotion: (None),
+                };
+                if let Some((king, rook)) = castle_by_rook_target(position, prev_square, square) {
+                    // the king may still follow, even if the rook could go there on its own
+                    (State::CastlingRookDown(king, rook), None)
+                } else if position.is_legal(&mv) {
+                    info!("MOVE COMMITTED");
+                    (State::Idle, Some(mv))
+                } else if let Some((king, rook)) =
+                    castle_by_king_target(position, prev_square, square)
+                {
+                    (State::CastlingKingDown(king, rook), None)
+                } else {
+                    (State::InvalidMove(prev_square, square), None)
+                }
+            }
+        }
+        State::EnemyPU(prev_square) => {
+            let attackers = position
+                .board()
+                .attacks_to(prev_square, color, occupied)
+                .with(en_passant_attackers_of(position, prev_square));
+            if prev_square == square {
+                (State::Idle, None)
+            } else if !attackers.contains(square)
+                || enemies.contains(square)
+                || (position.board().role_at(square).unwrap() == Role::King
+                    && position
+                        .king_attackers(prev_square, color.other(), occupied)
+                        .any())
+            {
+                (State::InvalidPiecePU(Some(prev_square), square), None)
+            } else if attackers.contains(square) {
+                (State::FriendlyAndEnemyPU(square, prev_square), None)
+            } else {
+                (State::Error, None)
+            }
+        }
+        State::FriendlyAndEnemyPU(prev_friendly_square, prev_enemy_square) => {
+            let role_picked_up = position.board().role_at(prev_friendly_square).unwrap();
+            let en_passant = en_passant_captures(position)
+                .into_iter()
+                .find(|(mv, victim)| {
+                    mv.from() == Some(prev_friendly_square)
+                        && mv.to() == square
+                        && *victim == prev_enemy_square
+                });
+            if square == prev_friendly_square {
+                (State::EnemyPU(prev_enemy_square), None)
+            } else if let Some((mv, _)) = en_passant {
+                info!("CAPTURED EN PASSANT");
+                (State::Idle, Some(mv))
+            } else if square == prev_enemy_square {
+                let promotes = role_picked_up == Role::Pawn
+                    && (square.rank() == Rank::First || square.rank() == Rank::Eighth);
+                let mv = Move::Normal {
+                    role: (role_picked_up),
+                    from: (prev_friendly_square),
+                    capture: (position.board().role_at(prev_enemy_square)),
+                    to: (square),
+                    promotion: promotes.then_some(Role::Queen), //assuming player will pick queen
+                };
+                // an en passant victim is lifted from a square the pawn
+                // cannot land on
+                if position.is_legal(&mv) {
+                    info!("CAPTURED");
+                    if promotes {
+                        info!("PROMOTED");
+                    }
+                    (State::Idle, Some(mv))
+                } else {
+                    (State::InvalidMove(prev_friendly_square, square), None)
+                }
+            } else if menu_role(position, prev_friendly_square, square).is_some() {
+                (
+                    State::PromotionSelected(prev_friendly_square, Some(prev_enemy_square), square),
+                    None,
+                )
+            } else {
+                (State::Error, None)
+            }
+        }
+        State::PromotionSelected(pawn_square, enemy_square, menu_square) => {
+            if square == menu_square {
+                let role = menu_role(position, pawn_square, menu_square).unwrap();
+                (State::PromotionPU(pawn_square, enemy_square, role), None)
+            } else {
+                (State::Error, None)
+            }
+        }
+        State::PromotionPU(pawn_square, enemy_square, role) => {
+            let can_capture = position
+                .board()
+                .attacks_from(pawn_square)
+                .intersect(enemies);
+            if menu_role(position, pawn_square, square).is_some() {
+                //changing our mind
+                (
+                    State::PromotionSelected(pawn_square, enemy_square, square),
+                    None,
+                )
+            } else if let Some(enemy_square) = enemy_square {
+                if square == enemy_square {
+                    let mv = Move::Normal {
+                        role: Role::Pawn,
+                        from: pawn_square,
+                        capture: position.board().role_at(enemy_square),
+                        to: square,
+                        promotion: Some(role),
+                    };
+                    if position.is_legal(&mv) {
+                        info!("PROMOTED");
+                        (State::Idle, Some(mv))
+                    } else {
+                        (State::Error, None)
+                    }
+                } else if square == pawn_square {
+                    (State::EnemyPU(enemy_square), None)
+                } else {
+                    (State::Error, None)
+                }
+            } else if square == pawn_square {
+                (State::Idle, None)
+            } else if can_capture.contains(square) {
+                (State::PromotionPU(pawn_square, Some(square), role), None)
+            } else if enemies.contains(square) || friendlies.contains(square) {
+                (State::InvalidPiecePU(Some(pawn_square), square), None)
+            } else {
+                let mv = Move::Normal {
+                    role: Role::Pawn,
+                    from: pawn_square,
+                    capture: None,
+                    to: square,
+                    promotion: Some(role),
+                };
+                if position.is_legal(&mv) {
+                    info!("PROMOTED");
+                    (State::Idle, Some(mv))
+                } else {
+                    (State::InvalidMove(pawn_square, square), None)
+                }
+            }
+        }
+        State::Castling(king_square, rook_square) => {
+            let (king_target, rook_target) = castling_targets(color, king_square, rook_square);
+            if square == king_target {
+                (
+                    State::CastlingPutRookDown(king_square, rook_square, rook_target),
+                    None,
+                )
+            } else if square == rook_target {
+                (
+                    State::CastlingPutKingDown(king_square, rook_square, king_target),
+                    None,
+                )
+            } else if square == king_square {
+                (State::FriendlyPU(rook_square), None)
+            } else if square == rook_square {
+                (State::FriendlyPU(king_square), None)
+            } else {
+                (State::Error, None)
+            }
+        }
+        State::CastlingPutRookDown(king_square, rook_square, target_square)
+        | State::CastlingPutKingDown(king_square, rook_square, target_square) => {
+            if square == target_square {
+                let mv = Move::Castle {
+                    king: king_square,
+                    rook: rook_square,
+                };
+                info!("CASTLED");
+                (State::Idle, Some(mv))
+            } else {
+                (State::Error, None)
+            }
+        }
+        State::CastlingKingDown(king_square, rook_square) => {
+            let (king_target, rook_target) = castling_targets(color, king_square, rook_square);
+            if square == rook_square {
+                (
+                    State::CastlingPutRookDown(king_square, rook_square, rook_target),
+                    None,
+                )
+            } else if square == king_target {
+                (State::FriendlyPU(king_square), None)
+            } else {
+                (State::Error, None)
+            }
+        }
+        State::CastlingRookDown(king_square, rook_square) => {
+            let (king_target, rook_target) = castling_targets(color, king_square, rook_square);
+            if square == king_square {
+                (
+                    State::CastlingPutKingDown(king_square, rook_square, king_target),
+                    None,
+                )
+            } else if square == rook_target {
+                (State::FriendlyPU(rook_square), None)
+            } else {
+                // another piece was touched instead of the king
+                let mv = Move::Normal {
+                    role: Role::Rook,
+                    from: rook_square,
+                    capture: None,
+                    to: rook_target,
+                    promotion: None,
+                };
+                if position.is_legal(&mv) {
+                    info!("MOVE COMMITTED");
+                    (State::Idle, Some(mv))
+                } else {
+                    (State::Error, None)
+                }
+            }
+        }
+        State::InvalidPiecePU(prev_prev_square, prev_square) => {
+            if square == prev_square && prev_prev_square.is_none() {
+                (State::Idle, None)
+            } else if square == prev_square && friendlies.contains(prev_prev_square.unwrap()) {
+                (State::FriendlyPU(prev_prev_square.unwrap()), None)
+            } else if square == prev_square && enemies.contains(prev_prev_square.unwrap()) {
+                (State::EnemyPU(prev_prev_square.unwrap()), None)
+            } else {
+                (State::Error, None)
+            }
+        }
+        State::InvalidMove(prev_prev_square, prev_square) => {
+            if square == prev_square {
+                (State::FriendlyPU(prev_prev_square), None)
+            } else {
+                (State::Error, None)
+            }
+        }
+        State::Error => (State::Error, None),
+    }
+}
+
+/// Finds the squares the king and rook of `color` land on when castling
+/// with the king on `king` and the rook on `rook`.
+#[must_use]
+pub fn castling_targets(color: Color, king: Square, rook: Square) -> (Square, Square) {
+    let side = CastlingSide::from_king_side(king < rook);
+    (side.king_to(color), side.rook_to(color))
+}
+
+/// Finds the legal castle, as `(king, rook)`, that puts the king on `from`
+/// onto `target`.
+fn castle_by_king_target(
+    position: &Chess,
+    from: Square,
+    target: Square,
+) -> Option<(Square, Square)> {
+    legal_castles(position).find(|&(king, rook)| {
+        king == from && castling_targets(position.turn(), king, rook).0 == target
+    })
+}
+
+/// Finds the legal castle, as `(king, rook)`, that puts the rook on `from`
+/// onto `target`.
+fn castle_by_rook_target(
+    position: &Chess,
+    from: Square,
+    target: Square,
+) -> Option<(Square, Square)> {
+    legal_castles(position).find(|&(king, rook)| {
+        rook == from && castling_targets(position.turn(), king, rook).1 == target
+    })
+}
+
+/// Lists the legal castles as `(king, rook)` pairs.
+pub(crate) fn legal_castles(position: &Chess) -> impl Iterator<Item = (Square, Square)> {
+    position
+        .castling_moves(CastlingSide::KingSide)
+        .into_iter()
+        .chain(position.castling_moves(CastlingSide::QueenSide))
+        .filter_map(|mv| match mv {
+            Move::Castle { king, rook } => Some((king, rook)),
+            _ => None,
+        })
+}
+
+/// Lists the legal en passant captures, each with the square of the pawn it
+/// removes.
+#[must_use]
+pub fn en_passant_captures(position: &Chess) -> Vec<(Move, Square)> {
+    position
+        .en_passant_moves()
+        .into_iter()
+        .filter_map(|mv| {
+            let victim = Square::from_coords(mv.to().file(), mv.from()?.rank());
+            Some((mv, victim))
+        })
+        .collect()
+}
+
+/// The pawns that the piece on `from` can take en passant.
+pub(crate) fn en_passant_victims_of(position: &Chess, from: Square) -> Bitboard {
+    en_passant_captures(position)
+        .into_iter()
+        .filter(|(mv, _)| mv.from() == Some(from))
+        .map(|(_, victim)| victim)
+        .collect()
+}
+
+/// The friendly pawns that can take the pawn on `victim` en passant.
+pub(crate) fn en_passant_attackers_of(position: &Chess, victim: Square) -> Bitboard {
+    en_passant_captures(position)
+        .into_iter()
+        .filter(|&(_, ep_victim)| ep_victim == victim)
+        .filter_map(|(mv, _)| mv.from())
+        .collect()
+}
+
+/// The roles a pawn can promote to, in the order they appear in the
+/// [`promotion_menu`].
+pub const PROMOTION_ROLES: [Role; 4] = [Role::Queen, Role::Rook, Role::Bishop, Role::Knight];
+
+/// Lays out the promotion menu for the pawn on `from`.
+///
+/// While a promoting pawn is in hand, putting it down on one of these squares
+/// and lifting it again selects the role it promotes to. The menu takes the
+/// empty squares nearest to `from` that are not on the promotion rank, paired
+/// with [`PROMOTION_ROLES`] in order. It is empty unless `from` holds a pawn
+/// with a legal promotion.
+#[must_use]
+pub fn promotion_menu(position: &Chess, from: Square) -> Vec<(Square, Role)> {
+    let promotes = position
+        .legal_moves()
+        .iter()
+        .any(|mv| mv.from() == Some(from) && mv.is_promotion());
+    if !promotes {
+        return Vec::new();
+    }
+    let last_rank = Bitboard::from_rank(position.turn().fold_wb(Rank::Eighth, Rank::First));
+    let mut squares: Vec<Square> = (!position.board().occupied() & !last_rank)
+        .into_iter()
+        .collect();
+    squares.sort_by_key(|&square| {
+        let manhattan = from.file().distance(square.file()) + from.rank().distance(square.rank());
+        (from.distance(square), manhattan, square)
+    });
+    squares.into_iter().zip(PROMOTION_ROLES).collect()
+}
+
+/// Looks up the role selected by putting the pawn from `from` on `square`.
+fn menu_role(position: &Chess, from: Square, square: Square) -> Option<Role> {
+    promotion_menu(position, from)
+        .into_iter()
+        .find(|&(menu_square, _)| menu_square == square)
+        .map(|(_, role)| role)
+}
+
+/// A single reed switch toggle between two sensor readings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SquareChange {
+    pub square: Square,
+    /// `true` if a piece was lifted off the square, `false` if one was put down.
+    pub lifted: bool,
+}
+
+/// Lists the squares that differ between the `prev` and `current` readings.
+///
+/// Lifted squares come before placed ones, each in ascending square order.
+#[must_use]
+pub fn get_square_changes(prev: Bitboard, current: Bitboard) -> Vec<SquareChange> {
+    let lifted = (prev & !current).into_iter().map(|square| SquareChange {
+        square,
+        lifted: true,
+    });
+    let placed = (current & !prev).into_iter().map(|square| SquareChange {
+        square,
+        lifted: false,
+    });
+    lifted.chain(placed).collect()
+}
+
+/// Advances the state machine by every change seen in one sensor poll.
+///
+/// The order in which the switches toggled within a poll is unknown, so the
+/// changes are applied one at a time, each time picking the change that makes
+/// the most progress: committing a move beats a regular state, which beats an
+/// invalid pickup or placement, which beats [`State::Error`]. Ties go to
+/// lifts before placements, friendly pieces before enemy pieces, and then the
+/// lower square.
+///
+/// Changes left over once a move is committed are not applied, and are
+/// returned so the caller can see them again against the position after the
+/// move.
+///
+/// # Panics
+///
+/// Panics under the same conditions as [`update_state`].
+#[must_use]
+pub fn update_state_batch(
+    position: &Chess,
+    changes: &[SquareChange],
+    state: State,
+) -> (State, Option<Move>, Vec<SquareChange>) {
+    let friendlies = position.us();
+    let mut pending = changes.to_vec();
+    pending.sort_by_key(|change| {
+        (
+            !change.lifted,
+            !friendlies.contains(change.square),
+            change.square,
+        )
+    });
+
+    let mut state = state;
+    while !pending.is_empty() {
+        let (idx, (next, mv)) = pending
+            .iter()
+            .map(|change| update_state(position, u32::from(change.square), state))
+            .enumerate()
+            .max_by_key(|(idx, (next, mv))| {
+                (progress(*next, mv.is_some()), std::cmp::Reverse(*idx))
+            })
+            .unwrap();
+        pending.remove(idx);
+        state = next;
+        if mv.is_some() {
+            if !pending.is_empty() {
+                warn!(
+                    "Leaving {n} square changes seen after committing a move",
+                    n = pending.len()
+                );
+            }
+            return (state, mv, pending);
+        }
+    }
+    (state, None, Vec::new())
+}
+
+/// Ranks how far a transition into `state` gets towards completing a move.
+const fn progress(state: State, committed: bool) -> u8 {
+    match state {
+        _ if committed => 3,
+        State::Error => 0,
+        State::InvalidPiecePU(_, _) | State::InvalidMove(_, _) => 1,
+        _ => 2,
+    }
+}
+
+/// Prints the name of the variant of `state` to stdout.
+pub fn print_state_name(state: State) {
+    match state {
+        State::Idle => println!("Idle"),
+        State::FriendlyPU(_) => println!("FriendlyPU"),
+        State::EnemyPU(_) => println!("EnemyPU"),
+        State::FriendlyAndEnemyPU(_, _) => println!("FriendlyAndEnemyPU"),
+        State::Castling(_, _) => println!("Castling"),
+        State::CastlingPutRookDown(_, _, _) => println!("CastlingPutRookDown"),
+        State::CastlingPutKingDown(_, _, _) => println!("CastlingPutKingDown"),
+        State::CastlingKingDown(_, _) => println!("CastlingKingDown"),
+        State::CastlingRookDown(_, _) => println!("CastlingRookDown"),
+        State::PromotionSelected(_, _, _) => println!("PromotionSelected"),
+        State::PromotionPU(_, _, _) => println!("PromotionPU"),
+        State::InvalidPiecePU(_, _) => println!("InvalidPiecePU"),
+        State::InvalidMove(_, _) => println!("InvalidMove"),
+        State::Error => println!("Error"),
+    }
+}
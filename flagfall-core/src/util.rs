@@ -0,0 +1,116 @@
+//! Square numbering, coordinate conversion and debug printing helpers.
+
+use shakmaty::{Bitboard, File, Rank, Square};
+
+/// Lists the numbers of the squares that differ between `prev` and `current`,
+/// in ascending order.
+#[must_use]
+pub fn get_changed_square_number(prev: Bitboard, current: u64) -> Vec<u32> {
+    prev.toggled(Bitboard(current))
+        .into_iter()
+        .map(square_to_number)
+        .collect()
+}
+
+/// Numbers squares from `0` (a1) to `63` (h8), rank by rank.
+#[must_use]
+pub fn square_to_number(square: Square) -> u32 {
+    u32::from(square)
+}
+
+/// Converts `rank` into its board y-coordinate, from `1.0` to `8.0`.
+#[must_use]
+pub const fn rank_to_float(rank: Rank) -> f64 {
+    match rank {
+        Rank::First => 1.0,
+        Rank::Second => 2.0,
+        Rank::Third => 3.0,
+        Rank::Fourth => 4.0,
+        Rank::Fifth => 5.0,
+        Rank::Sixth => 6.0,
+        Rank::Seventh => 7.0,
+        Rank::Eighth => 8.0,
+    }
+}
+
+/// Converts `file` into its board x-coordinate, from `1.0` to `8.0`.
+#[must_use]
+pub const fn file_to_float(file: File) -> f64 {
+    match file {
+        File::A => 1.0,
+        File::B => 2.0,
+        File::C => 3.0,
+        File::D => 4.0,
+        File::E => 5.0,
+        File::F => 6.0,
+        File::G => 7.0,
+        File::H => 8.0,
+    }
+}
+
+/// Prints the board part of a FEN to stderr, with square numbers alongside.
+///
+/// # Panics
+///
+/// Panics if `fen` describes more than eight ranks.
+pub fn print_board_from_fen(fen: &str) {
+    use std::fmt::Write;
+    static ENDLINES: [&str; 8] = [
+        "     0  1  2  3  4  5  6  7",
+        "     8  9 10 11 12 13 14 15\n",
+        "    16 17 18 19 20 21 22 23\n",
+        "    24 25 26 27 28 29 30 31\n",
+        "    32 33 34 35 36 37 38 39\n",
+        "    40 41 42 43 44 45 46 47\n",
+        "    48 49 50 51 52 53 54 55\n",
+        "    56 57 58 59 60 61 62 63\n",
+    ];
+    let mut rank = 7;
+    let mut output: String = String::new();
+    let mut counter = 0;
+    output.push(' ');
+    for c in fen.chars() {
+        if counter == 8 {
+            counter = 0;
+            output.push_str(ENDLINES[rank]);
+            rank -= 1;
+        }
+        match c {
+            c @ ('r' | 'R' | 'n' | 'N' | 'b' | 'B' | 'q' | 'Q' | 'k' | 'K' | 'p' | 'P') => {
+                write!(output, "{c} ").unwrap();
+                counter += 1;
+            }
+            n @ '1'..='8' => {
+                let n = n.to_digit(10).unwrap();
+                for _ in 0..n {
+                    output.push_str(". ");
+                }
+                counter += n;
+            }
+            _ => {
+                output.push(' ');
+            }
+        }
+    }
+    output.push_str(ENDLINES[0]);
+    eprintln!("{output}");
+}
+
+/// Prints `bitboard` to stderr as an 8x8 grid of bits, a1 at the bottom left.
+pub fn print_bitboard(bitboard: Bitboard) {
+    let y = format!("{bitboard:064b}");
+
+    let mut output: String = String::new();
+    let mut line = String::new();
+    for (counter, a) in y.chars().enumerate() {
+        if counter % 8 == 0 {
+            output.push_str(line.chars().rev().collect::<String>().as_str());
+            output.push('\n');
+            line = String::new();
+        }
+        line.push(a);
+        line.push(' ');
+    }
+    output.push_str(line.chars().rev().collect::<String>().as_str());
+    eprintln!("{}", output.as_str());
+}
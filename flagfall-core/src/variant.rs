@@ -0,0 +1,160 @@
+//! Starting positions for the supported variants.
+//!
+//! Chess960 starting positions are numbered from `0` to `959` using
+//! Scharnagl's numbering, in which the standard starting position is
+//! [`STANDARD_CHESS960_INDEX`]. Games can also start from any legal FEN, see
+//! [`StartPosition`].
+
+use std::fmt::Display;
+
+use shakmaty::{fen::Fen, CastlingMode, Chess, PositionError, Role};
+
+/// The number of Chess960 starting positions.
+pub const CHESS960_POSITIONS: u32 = 960;
+
+/// The Scharnagl number of the standard starting position.
+pub const STANDARD_CHESS960_INDEX: u32 = 518;
+
+/// Where the two knights go among the five squares left once the bishops and
+/// queen are placed, for each remainder of the knight digit.
+const KNIGHT_PLACEMENTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+/// Lays out the back rank of Chess960 starting position `index`, from the a
+/// file to the h file.
+///
+/// Returns `None` if `index` is not below [`CHESS960_POSITIONS`].
+#[must_use]
+pub fn chess960_back_rank(index: u32) -> Option<[Role; 8]> {
+    if index >= CHESS960_POSITIONS {
+        return None;
+    }
+    let mut rank: [Option<Role>; 8] = [None; 8];
+    let n = index as usize;
+
+    // light-squared bishop on b, d, f or h, then dark-squared on a, c, e or g
+    rank[(n % 4) * 2 + 1] = Some(Role::Bishop);
+    let n = n / 4;
+    rank[(n % 4) * 2] = Some(Role::Bishop);
+    let n = n / 4;
+
+    let empty = |rank: &[Option<Role>; 8]| -> Vec<usize> {
+        (0..8).filter(|&file| rank[file].is_none()).collect()
+    };
+    rank[empty(&rank)[n % 6]] = Some(Role::Queen);
+    let (first, second) = KNIGHT_PLACEMENTS[n / 6];
+    let free = empty(&rank);
+    rank[free[first]] = Some(Role::Knight);
+    rank[free[second]] = Some(Role::Knight);
+
+    // the king always sits between the rooks
+    for (file, role) in empty(&rank)
+        .into_iter()
+        .zip([Role::Rook, Role::King, Role::Rook])
+    {
+        rank[file] = Some(role);
+    }
+    Some(rank.map(Option::unwrap))
+}
+
+/// Builds Chess960 starting position `index`, with both sides free to castle
+/// either way.
+///
+/// Returns `None` if `index` is not below [`CHESS960_POSITIONS`].
+///
+/// # Panics
+///
+/// Panics if the generated position is illegal, which would be a bug.
+#[must_use]
+pub fn chess960_position(index: u32) -> Option<Chess> {
+    let back_rank = chess960_back_rank(index)?;
+    let white: String = back_rank.iter().map(|role| role.upper_char()).collect();
+    let black = white.to_lowercase();
+    let fen = format!("{black}/pppppppp/8/8/8/8/PPPPPPPP/{white} w KQkq - 0 1");
+    let position = fen
+        .parse::<Fen>()
+        .expect("[variant::chess960_position] Invalid FEN")
+        .into_position(CastlingMode::Chess960)
+        .expect("[variant::chess960_position] Illegal position");
+    Some(position)
+}
+
+/// How the board is set up before the first move.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum StartPosition {
+    /// The standard starting position.
+    #[default]
+    Standard,
+    /// The Chess960 starting position with the given number.
+    Chess960(u32),
+    /// Any position, such as a puzzle, an adjourned game or an endgame drill.
+    Fen(Fen),
+}
+
+/// Why a [`StartPosition`] cannot be played.
+#[derive(Debug)]
+pub enum StartPositionError {
+    /// The Chess960 position number is not below [`CHESS960_POSITIONS`].
+    OutOfRange(u32),
+    /// The FEN does not describe a legal position.
+    Illegal(Box<PositionError<Chess>>),
+}
+
+impl Display for StartPositionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::OutOfRange(index) => write!(
+                f,
+                "Chess960 position {index} is out of range, expected 0-{}",
+                CHESS960_POSITIONS - 1
+            ),
+            Self::Illegal(e) => write!(f, "illegal starting position: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for StartPositionError {}
+
+impl StartPosition {
+    /// The castling notation moves are played in from this position.
+    ///
+    /// FENs whose castling rights only make sense in Chess960 use Chess960
+    /// castling.
+    #[must_use]
+    pub fn castling_mode(&self) -> CastlingMode {
+        match self {
+            Self::Standard => CastlingMode::Standard,
+            Self::Chess960(_) => CastlingMode::Chess960,
+            Self::Fen(fen) => CastlingMode::detect(fen.as_setup()),
+        }
+    }
+
+    /// Builds the position the game starts from.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`StartPositionError`] if the Chess960 number is out of range
+    /// or the FEN is not a legal position.
+    pub fn to_position(&self) -> Result<Chess, StartPositionError> {
+        match self {
+            Self::Standard => Ok(Chess::default()),
+            Self::Chess960(index) => {
+                chess960_position(*index).ok_or(StartPositionError::OutOfRange(*index))
+            }
+            Self::Fen(fen) => fen
+                .clone()
+                .into_position(self.castling_mode())
+                .map_err(|e| StartPositionError::Illegal(Box::new(e))),
+        }
+    }
+}
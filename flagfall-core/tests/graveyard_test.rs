@@ -0,0 +1,84 @@
+extern crate flagfall_core;
+
+use flagfall_core::graveyard::{captured_square, SLOTS};
+use flagfall_core::Graveyard;
+use shakmaty::{fen::Fen, uci::Uci, CastlingMode, Chess, Color, Piece, Position, Role, Square};
+
+fn _position(fen: &str) -> Chess {
+    fen.parse::<Fen>()
+        .expect("[graveyard_test::position] Invalid FEN")
+        .into_position(CastlingMode::Standard)
+        .expect("[graveyard_test::position] Illegal position")
+}
+
+fn _piece(color: Color, role: Role) -> Piece {
+    Piece { color, role }
+}
+
+#[test]
+fn test_slots_line_the_edges() {
+    assert_eq!(Graveyard::point(Color::White, 0), (0.0, 8.5));
+    assert_eq!(Graveyard::point(Color::White, SLOTS - 1), (0.0, 1.0));
+    assert_eq!(Graveyard::point(Color::Black, 0), (9.0, 0.5));
+    assert_eq!(Graveyard::point(Color::Black, SLOTS - 1), (9.0, 8.0));
+}
+
+#[test]
+fn test_capture_records_the_piece() {
+    let mut graveyard = Graveyard::new();
+    let pos = _position("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3");
+    let quiet = "g1f3".parse::<Uci>().unwrap().to_move(&pos).unwrap();
+    assert_eq!(graveyard.capture(pos.board(), &quiet), None);
+
+    let en_passant = "e5f6".parse::<Uci>().unwrap().to_move(&pos).unwrap();
+    assert_eq!(captured_square(&en_passant), Some(Square::F5));
+    assert_eq!(graveyard.capture(pos.board(), &en_passant), Some(0));
+    assert_eq!(graveyard.get(Color::Black, 0), Some(Role::Pawn));
+    assert_eq!(graveyard.count(Color::Black), 1);
+    assert_eq!(graveyard.count(Color::White), 0);
+    assert_eq!(graveyard.next_slot(Color::Black), Some(1));
+}
+
+#[test]
+fn test_take_frees_the_slot() {
+    let mut graveyard = Graveyard::new();
+    for role in [Role::Knight, Role::Pawn, Role::Queen] {
+        graveyard.park(_piece(Color::White, role));
+    }
+    assert_eq!(graveyard.find(_piece(Color::White, Role::Queen)), Some(2));
+    assert_eq!(graveyard.find(_piece(Color::Black, Role::Queen)), None);
+
+    assert_eq!(graveyard.take(_piece(Color::White, Role::Knight)), Some(0));
+    assert_eq!(graveyard.take(_piece(Color::White, Role::Knight)), None);
+    assert_eq!(graveyard.next_slot(Color::White), Some(0));
+    assert_eq!(
+        graveyard.pieces().collect::<Vec<_>>(),
+        vec![
+            (_piece(Color::White, Role::Pawn), 1),
+            (_piece(Color::White, Role::Queen), 2)
+        ]
+    );
+
+    assert_eq!(graveyard.park(_piece(Color::White, Role::Bishop)), Some(0));
+    for _ in 3..SLOTS {
+        assert!(graveyard.park(_piece(Color::White, Role::Pawn)).is_some());
+    }
+    assert_eq!(graveyard.park(_piece(Color::White, Role::Pawn)), None);
+}
+
+#[test]
+fn test_promotion_swaps_the_pawn() {
+    let pos = _position("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1");
+    let mut graveyard = Graveyard::new();
+    graveyard.park(_piece(Color::White, Role::Queen));
+
+    let knight = "b7b8n".parse::<Uci>().unwrap().to_move(&pos).unwrap();
+    let before = graveyard.clone();
+    assert_eq!(graveyard.play(pos.board(), &knight), Some(Role::Knight));
+    assert_eq!(graveyard, before);
+
+    let queen = "b7b8q".parse::<Uci>().unwrap().to_move(&pos).unwrap();
+    assert_eq!(graveyard.play(pos.board(), &queen), None);
+    assert_eq!(graveyard.get(Color::White, 0), None);
+    assert_eq!(graveyard.get(Color::White, 1), Some(Role::Pawn));
+}
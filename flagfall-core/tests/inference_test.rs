@@ -0,0 +1,82 @@
+extern crate flagfall_core;
+
+use flagfall_core::inference::{infer_move, infer_moves};
+use shakmaty::{fen::Fen, Bitboard, CastlingMode, Chess, Move, Position, Role, Square};
+
+fn _position(fen: &str) -> Chess {
+    fen.parse::<Fen>()
+        .expect("[inference_test::position] Invalid FEN")
+        .into_position(CastlingMode::Standard)
+        .expect("[inference_test::position] Illegal position")
+}
+
+fn _toggled(bitboard: Bitboard, squares: &[Square]) -> Bitboard {
+    squares
+        .iter()
+        .fold(bitboard, |acc, &sq| acc ^ Bitboard::from_square(sq))
+}
+
+#[test]
+fn test_infer_castling() {
+    let pos = _position("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    let last = pos.board().occupied();
+    let current = _toggled(last, &[Square::E1, Square::H1, Square::G1, Square::F1]);
+    assert_eq!(
+        infer_move(&pos, last, current),
+        Some(Move::Castle {
+            king: Square::E1,
+            rook: Square::H1
+        })
+    );
+}
+
+#[test]
+fn test_infer_en_passant() {
+    let pos = _position("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3");
+    let last = pos.board().occupied();
+    let current = _toggled(last, &[Square::E5, Square::F5, Square::F6]);
+    assert_eq!(
+        infer_move(&pos, last, current),
+        Some(Move::EnPassant {
+            from: Square::E5,
+            to: Square::F6
+        })
+    );
+}
+
+#[test]
+fn test_infer_capture_in_one_motion() {
+    // Only the lifted square changes when a piece replaces its victim.
+    let pos = _position("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2");
+    let last = pos.board().occupied();
+    let current = _toggled(last, &[Square::E4]);
+    assert_eq!(
+        infer_move(&pos, last, current).map(|mv| (mv.from(), mv.to())),
+        Some((Some(Square::E4), Square::D5))
+    );
+}
+
+#[test]
+fn test_infer_ambiguous_or_stale() {
+    // The e4 pawn can take on d5 or f5, which look identical to the sensors.
+    let pos = _position("rnbqkbnr/ppp1p1pp/8/3p1p2/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 3");
+    let last = pos.board().occupied();
+    let current = _toggled(last, &[Square::E4]);
+    assert_eq!(infer_moves(&pos, last, current).len(), 2);
+    assert_eq!(infer_move(&pos, last, current), None);
+
+    // A baseline that does not match the position is rejected outright.
+    assert!(infer_moves(&pos, current, last).is_empty());
+}
+
+#[test]
+fn test_infer_promotion_defaults_to_queen() {
+    let pos = _position("8/4P3/8/8/8/8/k7/4K3 w - - 0 1");
+    let last = pos.board().occupied();
+    let current = _toggled(last, &[Square::E7, Square::E8]);
+    assert_eq!(infer_moves(&pos, last, current).len(), 4);
+    assert_eq!(
+        infer_move(&pos, last, current).and_then(|mv| mv.promotion()),
+        Some(Role::Queen)
+    );
+}
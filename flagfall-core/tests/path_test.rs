@@ -0,0 +1,241 @@
+extern crate flagfall_core;
+
+use flagfall_core::path::{plan_path, Obstacles, Point};
+use flagfall_core::{
+    carry, check_steps, move_to_steps, split_steps, standing_pieces, Graveyard, Step, StepError,
+};
+use shakmaty::{fen::Fen, uci::Uci, CastlingMode, Chess, Color, Piece, Position, Role};
+
+/// How close a carried piece may pass to a standing one, as on the board.
+const CLEARANCE: f64 = 0.35;
+
+fn _position(fen: &str) -> Chess {
+    fen.parse::<Fen>()
+        .expect("[path_test::position] Invalid FEN")
+        .into_position(CastlingMode::Standard)
+        .expect("[path_test::position] Illegal position")
+}
+
+fn _distance(a: Point, b: Point) -> f64 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+/// Carries out `steps` on pieces standing at `standing`, asserting no carried
+/// piece passes too close to another. Returns where the pieces end up.
+fn _assert_clear(steps: &[Step], mut standing: Vec<Point>) -> Vec<Point> {
+    let mut carried = false;
+    let mut at = (steps[0].x, steps[0].y);
+    for step in steps {
+        let next = (step.x, step.y);
+        if !step.magnet {
+            if carried {
+                standing.push(at);
+            }
+            let index = standing
+                .iter()
+                .position(|&piece| _distance(piece, next) < 0.25)
+                .expect("[path_test::assert_clear] No piece to pick up");
+            standing.swap_remove(index);
+            carried = true;
+        } else {
+            for i in 0..=20 {
+                let t = f64::from(i) / 20.0;
+                let point = (
+                    (next.0 - at.0).mul_add(t, at.0),
+                    (next.1 - at.1).mul_add(t, at.1),
+                );
+                for &piece in &standing {
+                    assert!(
+                        _distance(point, piece) >= CLEARANCE,
+                        "carried past {piece:?} at {point:?}"
+                    );
+                }
+            }
+        }
+        at = next;
+    }
+    standing.push(at);
+    standing
+}
+
+fn _standing(pos: &Chess) -> Vec<Point> {
+    pos.board()
+        .occupied()
+        .into_iter()
+        .map(|square| {
+            (
+                f64::from(u32::from(square.file()) + 1),
+                f64::from(u32::from(square.rank()) + 1),
+            )
+        })
+        .collect()
+}
+
+fn _steps(pos: &Chess, uci: &str, graveyard: &Graveyard) -> Vec<Step> {
+    let mv = uci.parse::<Uci>().unwrap().to_move(pos).unwrap();
+    move_to_steps(&mv, pos.board(), graveyard)
+}
+
+#[test]
+fn test_knight_keeps_to_the_lanes() {
+    let pos = Chess::default();
+    let steps = _steps(&pos, "g1f3", &Graveyard::new());
+    assert!(steps.iter().skip(1).all(|step| step.magnet));
+    let end = _assert_clear(&steps, _standing(&pos));
+    assert!(end.contains(&(6.0, 3.0)));
+}
+
+#[test]
+fn test_clear_diagonal_is_one_leg() {
+    let pos = _position("rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2");
+    let steps = _steps(&pos, "f1c4", &Graveyard::new());
+    assert_eq!(steps.len(), 2);
+    assert!((steps[1].x - 3.0).abs() < f64::EPSILON && (steps[1].y - 4.0).abs() < f64::EPSILON);
+}
+
+#[test]
+fn test_capture_passes_parked_pieces() {
+    let pos = _position("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2");
+    let mut graveyard = Graveyard::new();
+    for role in [Role::Knight, Role::Pawn] {
+        graveyard.park(Piece {
+            color: Color::Black,
+            role,
+        });
+    }
+    let steps = _steps(&pos, "e4d5", &graveyard);
+    let mut standing = _standing(&pos);
+    standing.extend([(9.0, 0.5), (9.0, 1.0)]);
+    let end = _assert_clear(&steps, standing);
+    assert!(end.contains(&(9.0, 1.5)));
+    assert!(end.contains(&(4.0, 5.0)));
+}
+
+#[test]
+fn test_blocked_route_goes_around() {
+    let mut obstacles = Obstacles::new();
+    obstacles.add((1.0, 2.0));
+    let route = plan_path((1.0, 1.0), (1.0, 3.0), &obstacles).unwrap();
+    assert!(route.len() > 1);
+    assert_eq!(route.last(), Some(&(1.0, 3.0)));
+
+    obstacles.remove((1.0, 2.0));
+    assert!(!obstacles.contains((1.0, 2.0)));
+    assert_eq!(
+        plan_path((1.0, 1.0), (1.0, 3.0), &obstacles),
+        Some(vec![(1.0, 3.0)])
+    );
+    assert_eq!(plan_path((1.0, 1.0), (10.0, 1.0), &obstacles), None);
+}
+
+#[test]
+fn test_boxed_in_piece_moves_blockers_aside() {
+    let mut obstacles = Obstacles::new();
+    let ring = [
+        (1.5, 1.5),
+        (1.5, 2.0),
+        (1.5, 2.5),
+        (2.0, 1.5),
+        (2.0, 2.5),
+        (2.5, 1.5),
+        (2.5, 2.0),
+        (2.5, 2.5),
+    ];
+    for piece in ring {
+        obstacles.add(piece);
+    }
+    obstacles.add((2.0, 2.0));
+    let before = obstacles.clone();
+    assert_eq!(plan_path((2.0, 2.0), (5.0, 5.0), &obstacles), None);
+
+    let steps = carry(&mut obstacles, (2.0, 2.0), (5.0, 5.0));
+    assert!(steps.iter().filter(|step| !step.magnet).count() > 1);
+    let after = check_steps(&steps, &before).unwrap();
+    assert_eq!(after, obstacles);
+    assert!(ring.iter().all(|&piece| after.contains(piece)));
+    assert!(after.contains((5.0, 5.0)) && !after.contains((2.0, 2.0)));
+}
+
+#[test]
+fn test_check_steps_against_the_board() {
+    let pos = Chess::default();
+    let mut obstacles = Obstacles::new();
+    for point in _standing(&pos) {
+        obstacles.add(point);
+    }
+    let step = |x, y, magnet| Step { x, y, magnet };
+
+    let bishop = [step(3.0, 1.0, false), step(5.0, 3.0, true)];
+    assert_eq!(
+        check_steps(&bishop, &obstacles),
+        Err(StepError::Collision((5.0, 3.0)))
+    );
+    let rook = [step(1.0, 1.0, false), step(1.0, 2.0, true)];
+    assert_eq!(
+        check_steps(&rook, &obstacles),
+        Err(StepError::Occupied((1.0, 2.0)))
+    );
+    let nothing = [step(4.0, 4.0, false), step(4.0, 5.0, true)];
+    assert_eq!(
+        check_steps(&nothing, &obstacles),
+        Err(StepError::NothingToCarry((4.0, 4.0)))
+    );
+
+    let steps = _steps(&pos, "b1c3", &Graveyard::new());
+    let after = check_steps(&steps, &obstacles).unwrap();
+    assert!(after.contains((3.0, 3.0)) && !after.contains((2.0, 1.0)));
+}
+
+#[test]
+fn test_promotion_fetches_the_parked_piece() {
+    let pos = _position("4k3/1P6/8/8/8/8/8/4K3 w - - 0 1");
+    let mut graveyard = Graveyard::new();
+    graveyard.park(Piece {
+        color: Color::White,
+        role: Role::Queen,
+    });
+
+    let steps = _steps(&pos, "b7b8q", &graveyard);
+    let after = check_steps(&steps, &standing_pieces(pos.board(), &graveyard)).unwrap();
+    assert!(after.contains((2.0, 8.0)) && !after.contains((2.0, 7.0)));
+    assert!(after.contains(Graveyard::point(Color::White, 1)));
+    assert!(!after.contains(Graveyard::point(Color::White, 0)));
+
+    // with no knight to fetch, the pawn goes up to be swapped by hand
+    let steps = _steps(&pos, "b7b8n", &graveyard);
+    assert_eq!(steps.len(), 2);
+    assert!((steps[1].x - 2.0).abs() < f64::EPSILON && (steps[1].y - 8.0).abs() < f64::EPSILON);
+}
+
+#[test]
+fn test_long_carry_is_split_at_waypoints() {
+    let mut obstacles = Obstacles::new();
+    for rank in 1..=6 {
+        obstacles.add((3.0, f64::from(rank)));
+        obstacles.add((5.0, f64::from(rank + 2)));
+    }
+    obstacles.add((1.0, 1.0));
+    obstacles.add((8.0, 1.0));
+    // the piece winds between the two walls, then another steps up
+    let mut steps = carry(&mut obstacles, (1.0, 1.0), (7.0, 8.0));
+    let detour = steps.len();
+    steps.extend(carry(&mut obstacles, (8.0, 1.0), (8.0, 2.0)));
+    let max = 4;
+    assert!(detour > max + 1);
+
+    let instructions = split_steps(&steps, max);
+    assert!(instructions.iter().all(|steps| steps.len() <= max));
+    let rejoined: Vec<Point> = instructions
+        .concat()
+        .iter()
+        .map(|step| (step.x, step.y))
+        .collect();
+    let expected: Vec<Point> = steps.iter().map(|step| (step.x, step.y)).collect();
+    assert_eq!(rejoined, expected);
+    // the carry is picked up again where it was left, and the second starts
+    // an instruction of its own
+    assert_eq!(instructions.len(), 3);
+    assert!(instructions[1][0].magnet);
+    assert!(!instructions[2][0].magnet);
+    assert_eq!(split_steps(&steps, steps.len()).len(), 1);
+}
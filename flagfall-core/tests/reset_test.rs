@@ -0,0 +1,76 @@
+extern crate flagfall_core;
+
+use flagfall_core::{check_steps, plan_reset, standing_pieces, Graveyard, ResetPlan};
+use shakmaty::{fen::Fen, san::San, CastlingMode, Chess, Color, Position, Role, Square};
+
+fn _position(fen: &str) -> Chess {
+    fen.parse::<Fen>()
+        .expect("[reset_test::position] Invalid FEN")
+        .into_position(CastlingMode::Standard)
+        .expect("[reset_test::position] Illegal position")
+}
+
+/// Plays `sans` from the starting position with every capture parked.
+fn _play(sans: &str) -> (Chess, Graveyard) {
+    let mut pos = Chess::default();
+    let mut graveyard = Graveyard::new();
+    for san in sans.split_whitespace() {
+        let mv = san.parse::<San>().unwrap().to_move(&pos).unwrap();
+        graveyard.play(pos.board(), &mv);
+        pos = pos.play(&mv).unwrap();
+    }
+    (pos, graveyard)
+}
+
+/// Plans the reset of `pos` and `graveyard` to the starting position and
+/// checks the steps can be carried out and end where the plan says.
+fn _reset(pos: &Chess, graveyard: &Graveyard) -> ResetPlan {
+    let start = Chess::default();
+    let plan = plan_reset(pos.board(), graveyard, start.board());
+    let after = check_steps(&plan.steps, &standing_pieces(pos.board(), graveyard)).unwrap();
+    let mut expected = start.board().clone();
+    for &square in &plan.missing {
+        expected.discard_piece_at(square);
+    }
+    assert_eq!(after, standing_pieces(&expected, &plan.graveyard));
+    plan
+}
+
+#[test]
+fn test_reset_fetches_captured_pieces() {
+    let (pos, graveyard) = _play("e4 d5 exd5 Qxd5 Nc3 Qxa2 Rxa2 Nf6 Nf3 Bg4 Bc4 Nc6 O-O");
+    assert_eq!(graveyard.count(Color::White), 2);
+    assert_eq!(graveyard.count(Color::Black), 2);
+
+    let plan = _reset(&pos, &graveyard);
+    assert!(plan.missing.is_empty());
+    assert_eq!(plan.graveyard, Graveyard::new());
+}
+
+#[test]
+fn test_reset_untangles_swapped_pieces() {
+    let pos = _position("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RBNQKNBR w - - 0 1");
+    let plan = _reset(&pos, &Graveyard::new());
+    assert!(plan.missing.is_empty());
+    assert_eq!(plan.graveyard, Graveyard::new());
+
+    assert!(
+        plan_reset(Chess::default().board(), &Graveyard::new(), pos.board())
+            .steps
+            .iter()
+            .any(|step| step.magnet)
+    );
+    assert!(plan_reset(pos.board(), &Graveyard::new(), pos.board())
+        .steps
+        .is_empty());
+}
+
+#[test]
+fn test_reset_leaves_what_it_cannot_fix() {
+    // a second queen from a promotion, and the pawn swapped for it by hand
+    let pos = _position("rnbqkbnr/pppppppp/8/8/3Q4/8/PPP1PPPP/RNBQKBNR w KQkq - 0 1");
+    let plan = _reset(&pos, &Graveyard::new());
+    assert_eq!(plan.missing, vec![Square::D2]);
+    assert_eq!(plan.graveyard.count(Color::White), 1);
+    assert_eq!(plan.graveyard.get(Color::White, 0), Some(Role::Queen));
+}
@@ -0,0 +1,488 @@
+extern crate flagfall_core;
+
+use flagfall_core::inference::infer_move;
+use flagfall_core::util::{get_changed_square_number, square_to_number};
+use flagfall_core::{
+    get_mismatch_rgb, get_rgb, get_square_changes, get_swap_rgb, move_to_steps, promotion_menu,
+    rgb_to_colours, rgb_to_str, update_state, update_state_batch, Graveyard, SquareChange, State,
+};
+use shakmaty::{fen::Fen, Bitboard, CastlingMode, Chess, Move, Position, Role, Square};
+
+fn _position(fen: &str) -> Chess {
+    fen.parse::<Fen>()
+        .expect("[state_test::position] Invalid FEN")
+        .into_position(CastlingMode::Standard)
+        .expect("[state_test::position] Illegal position")
+}
+
+/// Feeds `squares` into the state machine one by one, returning the final state
+/// and the last move committed on the way.
+fn _feed(position: &Chess, squares: &[Square]) -> (State, Option<Move>) {
+    let mut state = State::Idle;
+    let mut committed = None;
+    for &square in squares {
+        let (next, mv) = update_state(position, square_to_number(square), state);
+        state = next;
+        if mv.is_some() {
+            committed = mv;
+        }
+    }
+    (state, committed)
+}
+
+#[test]
+fn test_quiet_move() {
+    let pos = Chess::default();
+    let (state, mv) = _feed(&pos, &[Square::E2, Square::E4]);
+    assert_eq!(state, State::Idle);
+    assert_eq!(
+        mv,
+        Some(Move::Normal {
+            role: Role::Pawn,
+            from: Square::E2,
+            capture: None,
+            to: Square::E4,
+            promotion: None,
+        })
+    );
+}
+
+#[test]
+fn test_lift_and_replace() {
+    let pos = Chess::default();
+    assert_eq!(_feed(&pos, &[Square::G1, Square::G1]), (State::Idle, None));
+    assert_eq!(
+        _feed(&pos, &[Square::E2, Square::E5]),
+        (State::InvalidMove(Square::E2, Square::E5), None)
+    );
+}
+
+#[test]
+fn test_capture_either_lift_order() {
+    let pos = _position("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2");
+    let expected = Some(Move::Normal {
+        role: Role::Pawn,
+        from: Square::E4,
+        capture: Some(Role::Pawn),
+        to: Square::D5,
+        promotion: None,
+    });
+    assert_eq!(
+        _feed(&pos, &[Square::E4, Square::D5, Square::D5]),
+        (State::Idle, expected.clone())
+    );
+    assert_eq!(
+        _feed(&pos, &[Square::D5, Square::E4, Square::D5]),
+        (State::Idle, expected)
+    );
+}
+
+#[test]
+fn test_castling_rook_first() {
+    let pos = _position("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    let (state, mv) = _feed(&pos, &[Square::H1, Square::E1, Square::G1, Square::F1]);
+    assert_eq!(state, State::Idle);
+    assert_eq!(
+        mv,
+        Some(Move::Castle {
+            king: Square::E1,
+            rook: Square::H1
+        })
+    );
+}
+
+#[test]
+fn test_led_hints() {
+    let pos = Chess::default();
+    let idle = get_rgb(&pos, State::Idle);
+    assert!(idle.r.is_empty() && idle.g.is_empty() && idle.b.is_empty());
+
+    let knight = get_rgb(&pos, State::FriendlyPU(Square::G1));
+    assert_eq!(
+        knight.g,
+        Bitboard::from_square(Square::F3) | Bitboard::from_square(Square::H3)
+    );
+    assert_eq!(knight.b, Bitboard::from_square(Square::G1));
+}
+
+#[test]
+fn test_mismatch_hints() {
+    let expected = Chess::default().board().occupied();
+    let actual = expected ^ Bitboard::from_square(Square::E2) ^ Bitboard::from_square(Square::E4);
+    let rgb = get_mismatch_rgb(expected, actual);
+    assert_eq!(rgb.r, Bitboard::from_square(Square::E2));
+    assert!(rgb.g.is_empty());
+    assert_eq!(rgb.b, Bitboard::from_square(Square::E4));
+    assert!(rgb_to_str(rgb)
+        .split(' ')
+        .skip(2)
+        .all(|c| ["0", "16711680", "255"].contains(&c)));
+    // colours run from h8 down to a1
+    let colours = rgb_to_colours(rgb);
+    assert_eq!(colours.len(), 64);
+    assert_eq!(colours[63 - usize::from(Square::E2)], 0xFF_0000);
+    assert_eq!(colours[63 - usize::from(Square::E4)], 0x00_00FF);
+    assert_eq!(
+        get_mismatch_rgb(expected, expected),
+        get_rgb(&Chess::default(), State::Idle)
+    );
+}
+
+#[test]
+fn test_swap_hint() {
+    let rgb = get_swap_rgb(Square::B8, Role::Knight);
+    assert!(rgb.r.is_empty() && rgb.b.is_empty());
+    assert_eq!(rgb.g, Bitboard::from_square(Square::B8));
+    let rgb = get_swap_rgb(Square::B8, Role::Queen);
+    assert!([rgb.r, rgb.g, rgb.b]
+        .iter()
+        .all(|&channel| channel == Bitboard::from_square(Square::B8)));
+}
+
+#[test]
+fn test_changed_squares() {
+    let prev = Bitboard(0xffff_0000_0000_ffff);
+    let current = prev ^ Bitboard::from_square(Square::E2) ^ Bitboard::from_square(Square::E4);
+    assert_eq!(get_changed_square_number(prev, current.0), vec![12, 28]);
+}
+
+#[test]
+fn test_capture_steps_end_in_graveyard() {
+    let pos = _position("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2");
+    let mv = Move::Normal {
+        role: Role::Pawn,
+        from: Square::E4,
+        capture: Some(Role::Pawn),
+        to: Square::D5,
+        promotion: None,
+    };
+    assert!(pos.is_legal(&mv));
+    let steps = move_to_steps(&mv, pos.board(), &Graveyard::new());
+    let parked = steps
+        .iter()
+        .position(|s| (s.x - 9.0).abs() < f64::EPSILON)
+        .unwrap();
+    assert!(steps[..=parked].iter().skip(1).all(|s| s.magnet));
+    let last = steps.last().unwrap();
+    assert!((last.x - 4.0).abs() < f64::EPSILON && (last.y - 5.0).abs() < f64::EPSILON);
+}
+
+#[test]
+fn test_batch_quiet_move_in_one_poll() {
+    let pos = Chess::default();
+    let prev = pos.board().occupied();
+    let current = prev ^ Bitboard::from_square(Square::G1) ^ Bitboard::from_square(Square::F3);
+    let changes = get_square_changes(prev, current);
+    assert_eq!(
+        changes,
+        vec![
+            SquareChange {
+                square: Square::G1,
+                lifted: true
+            },
+            SquareChange {
+                square: Square::F3,
+                lifted: false
+            },
+        ]
+    );
+    let (state, mv, leftover) = update_state_batch(&pos, &changes, State::Idle);
+    assert_eq!(state, State::Idle);
+    assert_eq!(mv.map(|mv| mv.to()), Some(Square::F3));
+    assert!(leftover.is_empty());
+}
+
+#[test]
+fn test_batch_prefers_progress_over_error() {
+    let pos = _position("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    // Both lifted in one poll, enemy-free so lift order does not matter.
+    let lifts = [
+        SquareChange {
+            square: Square::H1,
+            lifted: true,
+        },
+        SquareChange {
+            square: Square::E1,
+            lifted: true,
+        },
+    ];
+    let (state, mv, _) = update_state_batch(&pos, &lifts, State::Idle);
+    assert_eq!((state, mv), (State::Castling(Square::E1, Square::H1), None));
+
+    // Rook and king land in the same poll, in whichever order.
+    let places = [
+        SquareChange {
+            square: Square::F1,
+            lifted: false,
+        },
+        SquareChange {
+            square: Square::G1,
+            lifted: false,
+        },
+    ];
+    let (state, mv, _) = update_state_batch(&pos, &places, state);
+    assert_eq!(state, State::Idle);
+    assert_eq!(
+        mv,
+        Some(Move::Castle {
+            king: Square::E1,
+            rook: Square::H1
+        })
+    );
+}
+
+#[test]
+fn test_batch_returns_changes_after_a_move() {
+    let pos = Chess::default();
+    // The reply is already under way in the poll that completes the move.
+    let changes = [
+        SquareChange {
+            square: Square::E2,
+            lifted: true,
+        },
+        SquareChange {
+            square: Square::E7,
+            lifted: true,
+        },
+        SquareChange {
+            square: Square::E4,
+            lifted: false,
+        },
+    ];
+    let (state, mv, leftover) = update_state_batch(&pos, &changes, State::Idle);
+    assert_eq!(state, State::Idle);
+    assert_eq!(mv.map(|mv| mv.to()), Some(Square::E4));
+    assert_eq!(leftover, vec![changes[1]]);
+}
+
+#[test]
+fn test_batch_changes_after_a_move_are_seen_next_turn() {
+    let pos = Chess::default();
+    let prev = pos.board().occupied();
+    // The next piece is already lifted in the poll that completes the move.
+    let current = prev
+        ^ Bitboard::from_square(Square::E2)
+        ^ Bitboard::from_square(Square::E4)
+        ^ Bitboard::from_square(Square::H2);
+    let (state, mv, leftover) =
+        update_state_batch(&pos, &get_square_changes(prev, current), State::Idle);
+    assert_eq!(state, State::Idle);
+    let reply = Move::Normal {
+        role: Role::Pawn,
+        from: Square::E7,
+        capture: None,
+        to: Square::E5,
+        promotion: None,
+    };
+    let pos = pos.play(&mv.unwrap()).unwrap().play(&reply).unwrap();
+
+    // Against the position after the reply, the reading differs by just
+    // what was left over.
+    let changes = get_square_changes(
+        pos.board().occupied(),
+        pos.board().occupied() ^ Bitboard::from_square(Square::H2),
+    );
+    assert_eq!(changes, leftover);
+    let (state, mv, _) = update_state_batch(&pos, &changes, State::Idle);
+    assert_eq!((state, mv), (State::FriendlyPU(Square::H2), None));
+}
+
+#[test]
+fn test_batch_capture_in_one_poll() {
+    let pos = _position("rnbqkbnr/ppp1pppp/8/3p4/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 0 2");
+    let prev = pos.board().occupied();
+    // The pawn was swapped for the one on d5 between two polls, so d5 never
+    // read empty and only e4 changed.
+    let current = prev ^ Bitboard::from_square(Square::E4);
+    let changes = get_square_changes(prev, current);
+    let (state, mv, _) = update_state_batch(&pos, &changes, State::Idle);
+    assert_eq!((state, mv), (State::FriendlyPU(Square::E4), None));
+    // The occupancy alone still gives the capture away.
+    let inferred = infer_move(&pos, prev, current).unwrap();
+    assert_eq!(
+        (inferred.from(), inferred.to()),
+        (Some(Square::E4), Square::D5)
+    );
+    assert!(inferred.is_capture());
+}
+
+#[test]
+fn test_promotion_menu() {
+    let pos = _position("8/4P3/8/8/8/8/k7/4K3 w - - 0 1");
+    let menu = promotion_menu(&pos, Square::E7);
+    assert_eq!(
+        menu,
+        vec![
+            (Square::E6, Role::Queen),
+            (Square::D7, Role::Rook),
+            (Square::F7, Role::Bishop),
+            (Square::D6, Role::Knight),
+        ]
+    );
+    assert!(promotion_menu(&pos, Square::E1).is_empty());
+
+    // The pawn sitting on the rook square lights it red.
+    let selected = get_rgb(&pos, State::PromotionSelected(Square::E7, None, Square::D7));
+    assert!(selected.r.contains(Square::D7) && !selected.g.contains(Square::D7));
+}
+
+#[test]
+fn test_under_promotion() {
+    let pos = _position("8/4P3/8/8/8/8/k7/4K3 w - - 0 1");
+    let (state, mv) = _feed(&pos, &[Square::E7, Square::D6, Square::D6]);
+    assert_eq!(
+        (state, mv),
+        (State::PromotionPU(Square::E7, None, Role::Knight), None)
+    );
+    let (state, mv) = _feed(&pos, &[Square::E7, Square::D6, Square::D6, Square::E8]);
+    assert_eq!(state, State::Idle);
+    assert_eq!(mv.and_then(|mv| mv.promotion()), Some(Role::Knight));
+
+    // Straight onto the last rank is still a queen.
+    let (_, mv) = _feed(&pos, &[Square::E7, Square::E8]);
+    assert_eq!(mv.and_then(|mv| mv.promotion()), Some(Role::Queen));
+}
+
+#[test]
+fn test_capturing_under_promotion() {
+    let pos = _position("3r4/4P3/8/8/8/8/k7/4K3 w - - 0 1");
+    let rook_square = promotion_menu(&pos, Square::E7)[1].0;
+    let (state, mv) = _feed(
+        &pos,
+        &[Square::D8, Square::E7, rook_square, rook_square, Square::D8],
+    );
+    assert_eq!(state, State::Idle);
+    assert_eq!(
+        mv,
+        Some(Move::Normal {
+            role: Role::Pawn,
+            from: Square::E7,
+            capture: Some(Role::Rook),
+            to: Square::D8,
+            promotion: Some(Role::Rook),
+        })
+    );
+}
+
+#[test]
+fn test_en_passant_either_lift_order() {
+    let pos = _position("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3");
+    let expected = Some(Move::EnPassant {
+        from: Square::E5,
+        to: Square::F6,
+    });
+    assert_eq!(
+        _feed(&pos, &[Square::E5, Square::F5, Square::F6]),
+        (State::Idle, expected.clone())
+    );
+    assert_eq!(
+        _feed(&pos, &[Square::F5, Square::E5, Square::F6]),
+        (State::Idle, expected)
+    );
+    // The d5 pawn did not just move, so it cannot be taken in passing.
+    assert_eq!(
+        _feed(&pos, &[Square::E5, Square::D5]).0,
+        State::InvalidPiecePU(Some(Square::E5), Square::D5)
+    );
+
+    // The victim's own square is not where the capturing pawn lands.
+    let pos = _position("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 2");
+    assert_eq!(
+        _feed(&pos, &[Square::E5, Square::D5, Square::D5]),
+        (State::InvalidMove(Square::E5, Square::D5), None)
+    );
+    assert_eq!(
+        _feed(&pos, &[Square::E5, Square::D5, Square::D6]),
+        (
+            State::Idle,
+            Some(Move::EnPassant {
+                from: Square::E5,
+                to: Square::D6,
+            })
+        )
+    );
+}
+
+#[test]
+fn test_en_passant_hints() {
+    let pos = _position("rnbqkbnr/ppp1p1pp/8/3pPp2/8/8/PPPP1PPP/RNBQKBNR w KQkq f6 0 3");
+    let lifted = get_rgb(&pos, State::FriendlyPU(Square::E5));
+    assert!(lifted.g.contains(Square::F6) && !lifted.r.contains(Square::F6));
+    assert!(lifted.r.contains(Square::F5));
+
+    let victim = get_rgb(&pos, State::EnemyPU(Square::F5));
+    assert_eq!(victim.g, Bitboard::from_square(Square::E5));
+
+    let both = get_rgb(&pos, State::FriendlyAndEnemyPU(Square::E5, Square::F5));
+    assert_eq!(both.g, Bitboard::from_square(Square::F6));
+}
+
+#[test]
+fn test_castling_all_orders() {
+    let pos = _position("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    let kingside = Some(Move::Castle {
+        king: Square::E1,
+        rook: Square::H1,
+    });
+    let queenside = Some(Move::Castle {
+        king: Square::E1,
+        rook: Square::A1,
+    });
+    for order in [
+        // king two squares first, the usual way
+        [Square::E1, Square::G1, Square::H1, Square::F1],
+        // both lifted, either one put down first
+        [Square::E1, Square::H1, Square::G1, Square::F1],
+        [Square::E1, Square::H1, Square::F1, Square::G1],
+        [Square::H1, Square::E1, Square::F1, Square::G1],
+    ] {
+        assert_eq!(_feed(&pos, &order), (State::Idle, kingside.clone()));
+    }
+    assert_eq!(
+        _feed(&pos, &[Square::E1, Square::C1, Square::A1, Square::D1]),
+        (State::Idle, queenside)
+    );
+
+    // The rook may go first, even onto a square it could reach on its own.
+    assert_eq!(
+        _feed(&pos, &[Square::H1, Square::F1]),
+        (State::CastlingRookDown(Square::E1, Square::H1), None)
+    );
+    assert_eq!(
+        _feed(&pos, &[Square::H1, Square::F1, Square::E1, Square::G1]),
+        (State::Idle, kingside)
+    );
+    // Touching another piece instead of the king leaves it a rook move.
+    assert_eq!(
+        _feed(&pos, &[Square::H1, Square::F1, Square::A1]),
+        (
+            State::Idle,
+            Some(Move::Normal {
+                role: Role::Rook,
+                from: Square::H1,
+                capture: None,
+                to: Square::F1,
+                promotion: None,
+            })
+        )
+    );
+}
+
+#[test]
+fn test_castling_hints() {
+    let pos = _position("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+    let (state, _) = _feed(&pos, &[Square::E1, Square::G1]);
+    assert_eq!(state, State::CastlingKingDown(Square::E1, Square::H1));
+    let rgb = get_rgb(&pos, state);
+    assert_eq!(
+        rgb.b,
+        Bitboard::from_square(Square::F1) | Bitboard::from_square(Square::H1)
+    );
+    assert_eq!(rgb.r, Bitboard::from_square(Square::F1));
+
+    let both = get_rgb(&pos, State::Castling(Square::E1, Square::H1));
+    assert_eq!(
+        both.r,
+        Bitboard::from_square(Square::F1) | Bitboard::from_square(Square::G1)
+    );
+}
@@ -0,0 +1,86 @@
+extern crate flagfall_core;
+
+use std::collections::HashSet;
+
+use flagfall_core::util::square_to_number;
+use flagfall_core::variant::{
+    chess960_back_rank, chess960_position, StartPosition, CHESS960_POSITIONS,
+    STANDARD_CHESS960_INDEX,
+};
+use flagfall_core::{move_to_steps, update_state, Graveyard, State};
+use shakmaty::{fen::Fen, CastlingMode, Chess, Color, Move, Position, Role, Square};
+
+fn _position_960(fen: &str) -> Chess {
+    fen.parse::<Fen>()
+        .expect("[variant_test::position_960] Invalid FEN")
+        .into_position(CastlingMode::Chess960)
+        .expect("[variant_test::position_960] Illegal position")
+}
+
+#[test]
+fn test_standard_index() {
+    let pos = chess960_position(STANDARD_CHESS960_INDEX).unwrap();
+    assert_eq!(pos.board(), Chess::default().board());
+    assert!(chess960_position(CHESS960_POSITIONS).is_none());
+}
+
+#[test]
+fn test_back_ranks_are_distinct_and_valid() {
+    let mut seen = HashSet::new();
+    for index in 0..CHESS960_POSITIONS {
+        let rank = chess960_back_rank(index).unwrap();
+        let files = |role| (0..8).filter(move |&file| rank[file] == role);
+        let bishops: Vec<usize> = files(Role::Bishop).collect();
+        assert_ne!(bishops[0] % 2, bishops[1] % 2, "index {index}");
+        let rooks: Vec<usize> = files(Role::Rook).collect();
+        let king = files(Role::King).next().unwrap();
+        assert!(rooks[0] < king && king < rooks[1], "index {index}");
+        assert!(seen.insert(rank));
+    }
+}
+
+#[test]
+fn test_chess960_castle_onto_rook_square() {
+    // The king lands where the rook stood and the rook where the king stood.
+    let pos = _position_960("4k3/8/8/8/8/8/8/5KR1 w G - 0 1");
+    let mut state = State::Idle;
+    let mut committed = None;
+    for square in [Square::F1, Square::G1, Square::G1, Square::F1] {
+        let (next, mv) = update_state(&pos, square_to_number(square), state);
+        state = next;
+        committed = committed.or(mv);
+    }
+    let castle = Move::Castle {
+        king: Square::F1,
+        rook: Square::G1,
+    };
+    assert_eq!((state, committed), (State::Idle, Some(castle.clone())));
+
+    // The rook has to be out of the way before the king can slide over.
+    let steps = move_to_steps(&castle, pos.board(), &Graveyard::new());
+    let first = steps[0];
+    assert!((first.x - 7.0).abs() < f64::EPSILON && !first.magnet);
+    assert!((steps[1].y - 0.5).abs() < f64::EPSILON);
+    let last = steps.last().unwrap();
+    assert!((last.x - 6.0).abs() < f64::EPSILON && (last.y - 1.0).abs() < f64::EPSILON);
+}
+
+#[test]
+fn test_start_from_fen() {
+    let endgame = StartPosition::Fen("8/8/4k3/8/8/4K3/4P3/8 b - - 0 1".parse().unwrap());
+    let pos = endgame.to_position().unwrap();
+    assert_eq!(pos.turn(), Color::Black);
+    assert_eq!(pos.board().occupied().count(), 3);
+    assert_eq!(endgame.castling_mode(), CastlingMode::Standard);
+
+    // Castling rights on a b-file rook only exist in Chess960.
+    let shredder = StartPosition::Fen("4k3/8/8/8/8/8/8/1R2K3 w B - 0 1".parse().unwrap());
+    assert_eq!(shredder.castling_mode(), CastlingMode::Chess960);
+    assert!(shredder.to_position().is_ok());
+
+    let two_kings = StartPosition::Fen("4k3/8/8/8/8/8/8/3KK3 w - - 0 1".parse().unwrap());
+    assert!(two_kings.to_position().is_err());
+    assert!(StartPosition::Chess960(CHESS960_POSITIONS)
+        .to_position()
+        .is_err());
+}
@@ -0,0 +1,56 @@
+//! Messages between the master program and the serial-communicator.
+//!
+//! Each [`BoardRequest`] gets exactly one [`BoardReply`], so the master
+//! program always knows which request a reply belongs to.
+
+use serde::{Deserialize, Serialize};
+
+/// The number of LEDs in the matrix, one per square.
+pub const LED_COUNT: usize = 64;
+
+/// One waypoint of the magnet carriage, in squares with the centre of a1 at
+/// `(1, 1)` and the centre of h8 at `(8, 8)`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct MagnetStep {
+    pub x: f32,
+    pub y: f32,
+    /// Whether the magnet is engaged while travelling to this waypoint.
+    pub magnet: bool,
+}
+
+/// A request from the master program for the board.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BoardRequest {
+    /// Wait for the reed switches to change, then read them.
+    Sense,
+    /// Read the reed switches once they have settled, for checking a setup.
+    Scan,
+    /// Move the magnet carriage through `steps` in order.
+    Magnet { steps: Vec<MagnetStep> },
+    /// Show one 24-bit colour per square, starting from h8 and ending at a1.
+    Leds { colours: Vec<u32> },
+    /// Close the connection to the board.
+    Quit,
+}
+
+/// The serial-communicator's reply to a [`BoardRequest`].
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BoardReply {
+    /// The reed switch occupancy, with bit `n` set when square `n` holds a
+    /// piece (a1 is square 0).
+    Sensors { occupancy: u64 },
+    /// The request was carried out.
+    Done,
+    /// The request was not carried out.
+    Error { message: String },
+    /// The board did not answer in time, even after `attempts` tries. Unlike
+    /// [`BoardReply::Error`], the request may still have been carried out,
+    /// such as a `Magnet` move whose acknowledgement was lost.
+    Timeout { attempts: u32 },
+    /// The connection to the board was lost and the request was not carried
+    /// out, or only partly. Later requests wait until the board is back, after
+    /// which the pieces should be checked again.
+    Disconnected { message: String },
+}
@@ -0,0 +1,151 @@
+//! Versioned, newline-delimited JSON frames.
+//!
+//! A frame is a JSON object on a single line with the protocol version in
+//! `v` next to the fields of the message itself, for example
+//! `{"v":1,"type":"move","uci":"e2e4","san":"e4"}`.
+
+use std::fmt::Display;
+use std::io::{self, BufRead, Write};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+/// The version of the message protocol spoken by this build.
+///
+/// Bump it whenever a message changes shape, so mismatched programs fail
+/// loudly instead of misreading each other.
+pub const PROTOCOL_VERSION: u32 = 1;
+
+#[derive(Serialize)]
+struct FrameOut<'a, T> {
+    v: u32,
+    #[serde(flatten)]
+    message: &'a T,
+}
+
+#[derive(Deserialize)]
+struct VersionOnly {
+    v: u32,
+}
+
+/// Why a frame could not be read or written.
+#[derive(Debug)]
+pub enum ProtocolError {
+    /// The underlying stream failed.
+    Io(io::Error),
+    /// The line is not a message of the expected kind, such as stray output
+    /// from a library or a message meant for another program.
+    Malformed {
+        line: String,
+        source: serde_json::Error,
+    },
+    /// The line is a message from a different protocol version.
+    Version { found: u32 },
+}
+
+impl Display for ProtocolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "protocol stream failed: {e}"),
+            Self::Malformed { line, source } => {
+                write!(f, "not a protocol message ({source}): {line}")
+            }
+            Self::Version { found } => write!(
+                f,
+                "protocol version mismatch: expected {PROTOCOL_VERSION}, found {found}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ProtocolError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(e) => Some(e),
+            Self::Malformed { source, .. } => Some(source),
+            Self::Version { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for ProtocolError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Serialises `message` into a frame, including the trailing newline.
+///
+/// # Panics
+///
+/// Panics if `message` cannot be represented as JSON, which cannot happen for
+/// the messages in this crate.
+#[must_use]
+pub fn encode<T: Serialize>(message: &T) -> String {
+    let mut line = serde_json::to_string(&FrameOut {
+        v: PROTOCOL_VERSION,
+        message,
+    })
+    .expect("[frame::encode] Message is not serialisable");
+    line.push('\n');
+    line
+}
+
+/// Parses one frame, with or without its trailing newline.
+///
+/// # Errors
+///
+/// Returns [`ProtocolError::Version`] if the frame is from another protocol
+/// version, and [`ProtocolError::Malformed`] if `line` is not a frame holding
+/// a `T`.
+pub fn decode<T: DeserializeOwned>(line: &str) -> Result<T, ProtocolError> {
+    let line = line.trim();
+    let malformed = |source| ProtocolError::Malformed {
+        line: line.to_string(),
+        source,
+    };
+    // check the version first, so a newer message is not reported as garbage
+    let VersionOnly { v } = serde_json::from_str(line).map_err(malformed)?;
+    if v != PROTOCOL_VERSION {
+        return Err(ProtocolError::Version { found: v });
+    }
+    // the messages themselves ignore the extra `v` field
+    serde_json::from_str(line).map_err(malformed)
+}
+
+/// Writes `message` to `writer` as one frame and flushes it.
+///
+/// # Errors
+///
+/// Returns [`ProtocolError::Io`] if writing or flushing fails.
+pub fn write_message<W: Write, T: Serialize>(
+    writer: &mut W,
+    message: &T,
+) -> Result<(), ProtocolError> {
+    writer.write_all(encode(message).as_bytes())?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Reads the next frame from `reader`, skipping blank lines.
+///
+/// Returns `Ok(None)` once `reader` is exhausted.
+///
+/// # Errors
+///
+/// Returns [`ProtocolError::Io`] if reading fails, and otherwise the errors
+/// of [`decode`]. The offending line has been consumed, so the caller can
+/// carry on reading after a bad line.
+pub fn read_message<R: BufRead, T: DeserializeOwned>(
+    reader: &mut R,
+) -> Result<Option<T>, ProtocolError> {
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        if !line.trim().is_empty() {
+            return decode(&line).map(Some);
+        }
+    }
+}
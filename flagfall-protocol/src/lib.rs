@@ -0,0 +1,21 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery)]
+
+//! Messages passed between the Flagfall programs.
+//!
+//! Every message is one line of JSON carrying the protocol version and a
+//! `type` tag, so a reader can tell a message from stray output and stay in
+//! step with the writer.
+//!
+//! - [`frame`] reads and writes versioned message lines.
+//! - [`opponent`] is spoken between the master program and the opponent
+//!   wrapper.
+//! - [`board`] is spoken between the master program and the
+//!   serial-communicator.
+
+pub mod board;
+pub mod frame;
+pub mod opponent;
+
+pub use board::{BoardReply, BoardRequest, MagnetStep, LED_COUNT};
+pub use frame::{decode, encode, read_message, write_message, ProtocolError, PROTOCOL_VERSION};
+pub use opponent::{Colour, OpponentMessage, PlayerMessage};
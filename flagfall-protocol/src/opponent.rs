@@ -0,0 +1,50 @@
+//! Messages between the master program and the opponent wrapper.
+//!
+//! The wrapper sends [`OpponentMessage`]s: prompts for the user while setting
+//! up, the colour the user plays, the opponent's moves, clocks and the end of
+//! the game. The master program answers with [`PlayerMessage`]s.
+
+use serde::{Deserialize, Serialize};
+
+/// A side of the board.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Colour {
+    White,
+    Black,
+}
+
+/// A message from the opponent wrapper to the master program.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OpponentMessage {
+    /// A question for the user, to be answered with
+    /// [`PlayerMessage::Answer`].
+    Prompt { text: String },
+    /// The game is set up and the user plays `colour`.
+    GameStart { colour: Colour },
+    /// The opponent played a move, given in UCI and in SAN before it was
+    /// played.
+    Move { uci: String, san: String },
+    /// The time left on each clock, in milliseconds.
+    Clock { white_ms: u64, black_ms: u64 },
+    /// The game is over. `winner` is `None` for a draw or an abandoned game.
+    GameEnd {
+        winner: Option<Colour>,
+        reason: String,
+    },
+    /// Something went wrong on the opponent's side.
+    Error { message: String },
+}
+
+/// A message from the master program to the opponent wrapper.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PlayerMessage {
+    /// The user's answer to the last [`OpponentMessage::Prompt`].
+    Answer { text: String },
+    /// The user played a move, given in UCI.
+    Move { uci: String },
+    /// The user is done, the wrapper should end the game and exit.
+    Quit,
+}
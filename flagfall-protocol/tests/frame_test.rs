@@ -0,0 +1,119 @@
+extern crate flagfall_protocol;
+
+use std::io::Cursor;
+
+use flagfall_protocol::{
+    decode, encode, read_message, write_message, BoardReply, BoardRequest, Colour, MagnetStep,
+    OpponentMessage, PlayerMessage, ProtocolError, PROTOCOL_VERSION,
+};
+
+fn _round_trip<T>(message: &T) -> T
+where
+    T: serde::Serialize + serde::de::DeserializeOwned,
+{
+    let line = encode(message);
+    assert!(line.ends_with('\n'));
+    assert_eq!(line.matches('\n').count(), 1);
+    decode(&line).expect("[frame_test::round_trip] Failed to decode own frame")
+}
+
+#[test]
+fn test_round_trip() {
+    let messages = [
+        OpponentMessage::Prompt {
+            text: "enter the challenge colour [white|black|random]:".to_string(),
+        },
+        OpponentMessage::GameStart {
+            colour: Colour::Black,
+        },
+        OpponentMessage::Move {
+            uci: "e1h1".to_string(),
+            san: "O-O".to_string(),
+        },
+        OpponentMessage::Clock {
+            white_ms: 300_000,
+            black_ms: 299_500,
+        },
+        OpponentMessage::GameEnd {
+            winner: None,
+            reason: "stalemate".to_string(),
+        },
+    ];
+    for message in &messages {
+        assert_eq!(&_round_trip(message), message);
+    }
+    assert_eq!(_round_trip(&PlayerMessage::Quit), PlayerMessage::Quit);
+
+    let steps = BoardRequest::Magnet {
+        steps: vec![
+            MagnetStep {
+                x: 4.5,
+                y: 1.5,
+                magnet: false,
+            },
+            MagnetStep {
+                x: 4.5,
+                y: 3.5,
+                magnet: true,
+            },
+        ],
+    };
+    assert_eq!(_round_trip(&steps), steps);
+    let reply = BoardReply::Sensors {
+        occupancy: 0xFFFF_0000_0000_FFFF,
+    };
+    assert_eq!(_round_trip(&reply), reply);
+}
+
+#[test]
+fn test_wire_format() {
+    let line = encode(&PlayerMessage::Move {
+        uci: "e2e4".to_string(),
+    });
+    assert_eq!(
+        line,
+        format!("{{\"v\":{PROTOCOL_VERSION},\"type\":\"move\",\"uci\":\"e2e4\"}}\n")
+    );
+    let request: BoardRequest = decode(r#"{"v":1,"type":"sense"}"#).unwrap();
+    assert_eq!(request, BoardRequest::Sense);
+}
+
+#[test]
+fn test_rejects_other_versions_and_stray_output() {
+    let newer = format!("{{\"v\":{},\"type\":\"quit\"}}", PROTOCOL_VERSION + 1);
+    assert!(matches!(
+        decode::<PlayerMessage>(&newer),
+        Err(ProtocolError::Version { found }) if found == PROTOCOL_VERSION + 1
+    ));
+    assert!(matches!(
+        decode::<PlayerMessage>("e2e4"),
+        Err(ProtocolError::Malformed { .. })
+    ));
+    // a valid frame of the wrong kind is not mistaken for one of ours
+    assert!(matches!(
+        decode::<BoardReply>(&encode(&OpponentMessage::GameStart {
+            colour: Colour::White
+        })),
+        Err(ProtocolError::Malformed { .. })
+    ));
+}
+
+#[test]
+fn test_reader_resyncs_after_bad_lines() {
+    let mut stream = Vec::new();
+    write_message(&mut stream, &BoardReply::Done).unwrap();
+    stream.extend_from_slice(b"\nDEBUG: motor stalled\n");
+    write_message(&mut stream, &BoardReply::Sensors { occupancy: 1 }).unwrap();
+    let mut reader = Cursor::new(stream);
+
+    assert_eq!(
+        read_message::<_, BoardReply>(&mut reader).unwrap(),
+        Some(BoardReply::Done)
+    );
+    assert!(read_message::<_, BoardReply>(&mut reader).is_err());
+    assert_eq!(
+        read_message::<_, BoardReply>(&mut reader).unwrap(),
+        Some(BoardReply::Sensors { occupancy: 1 })
+    );
+    assert_eq!(read_message::<_, BoardReply>(&mut reader).unwrap(), None);
+}
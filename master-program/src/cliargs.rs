@@ -0,0 +1,30 @@
+use std::path::PathBuf;
+
+use clap::Parser;
+use serial_communicator::options::ConnectionArgs;
+use shakmaty::fen::Fen;
+
+use crate::OPPONENT_WRAPPER_EXE_PATH;
+
+#[derive(Parser)]
+#[clap(author, version, about)]
+pub struct Cli {
+    /// Play Chess960 from the given starting position number (0-959), or a
+    /// random one if no number is given.
+    #[clap(long, value_name = "INDEX", num_args = 0..=1)]
+    #[allow(clippy::option_option)]
+    pub chess960: Option<Option<u32>>,
+    /// Start from the given position instead, such as a puzzle or an
+    /// adjourned game.
+    #[clap(long, value_name = "FEN", conflicts_with = "chess960")]
+    pub fen: Option<Fen>,
+    /// Run this opponent wrapper, or anything else that speaks its protocol.
+    #[clap(long, value_name = "PATH", default_value = OPPONENT_WRAPPER_EXE_PATH)]
+    pub opponent_wrapper: PathBuf,
+    /// Put the pieces back in the starting position with the magnet once the
+    /// game is over.
+    #[clap(long)]
+    pub reset: bool,
+    #[clap(flatten)]
+    pub connection: ConnectionArgs,
+}
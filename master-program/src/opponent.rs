@@ -0,0 +1,53 @@
+use std::io::BufReader;
+use std::process::{ChildStdin, ChildStdout};
+
+use anyhow::Context;
+use flagfall_protocol::{
+    read_message, write_message, OpponentMessage, PlayerMessage, ProtocolError,
+};
+use log::warn;
+
+/// The pipes to a running opponent wrapper.
+pub struct Opponent {
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Opponent {
+    pub fn new(stdin: ChildStdin, stdout: ChildStdout) -> Self {
+        Self {
+            stdin,
+            stdout: BufReader::new(stdout),
+        }
+    }
+
+    pub fn send(&mut self, message: &PlayerMessage) -> anyhow::Result<()> {
+        write_message(&mut self.stdin, message)
+            .with_context(|| format!("Failed to send {message:?} to opponent wrapper"))
+    }
+
+    /// Waits for the next message, skipping any output that is not a protocol
+    /// message.
+    pub fn recv(&mut self) -> anyhow::Result<OpponentMessage> {
+        loop {
+            match read_message(&mut self.stdout) {
+                Ok(Some(message)) => return Ok(message),
+                Ok(None) => anyhow::bail!("opponent wrapper closed its output"),
+                Err(e @ ProtocolError::Malformed { .. }) => warn!("ignoring {e}"),
+                Err(e) => return Err(e).with_context(|| "Bad message from opponent wrapper"),
+            }
+        }
+    }
+
+    /// Shows the wrapper's `text` to the user and sends back their answer.
+    pub fn answer_prompt(&mut self, text: &str) -> anyhow::Result<()> {
+        println!("{text}");
+        let mut user_input = String::new();
+        std::io::stdin()
+            .read_line(&mut user_input)
+            .with_context(|| "Failed to read the answer from stdin")?;
+        self.send(&PlayerMessage::Answer {
+            text: user_input.trim().to_string(),
+        })
+    }
+}
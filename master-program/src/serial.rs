@@ -0,0 +1,137 @@
+use std::time::Duration;
+
+use anyhow::bail;
+use flagfall_core::{rgb_to_colours, split_steps, Step, RGB};
+use log::{debug, warn};
+use serial_communicator::connection::{BoardConnection, BoardError};
+use serial_communicator::{LedFrame, MAX_MAGNET_STEPS};
+use shakmaty::Bitboard;
+
+/// How long the reed switches must hold still before a reading counts as
+/// where the user put the pieces down.
+const SETTLE_TIME: Duration = Duration::from_secs(1);
+
+/// The connection to the board, keeping track of whether it went away.
+pub struct SerialComms {
+    connection: BoardConnection,
+    /// Set when the board went away, until [`Self::take_outage`] is called.
+    outage: bool,
+}
+
+impl SerialComms {
+    pub const fn new(connection: BoardConnection) -> Self {
+        Self {
+            connection,
+            outage: false,
+        }
+    }
+
+    /// Whether the board went away since the last call, in which case the
+    /// pieces may have been moved in the meantime.
+    pub fn take_outage(&mut self) -> bool {
+        std::mem::take(&mut self.outage)
+    }
+
+    /// Notes that the board went away during `what`.
+    fn lost(&mut self, what: &str, e: &BoardError) {
+        warn!("{e} during {what}, waiting for it to come back");
+        self.outage = true;
+    }
+
+    /// Reads the reed switches, settled first if `scan` is set. If the board
+    /// goes away, this waits for it to come back and scans it.
+    pub async fn read_sensors(&mut self, mut scan: bool) -> anyhow::Result<Bitboard> {
+        loop {
+            let reading = if scan {
+                self.connection.scan().await
+            } else {
+                self.connection.read_sensors().await
+            };
+            match reading {
+                Ok(reading) => return Ok(reading),
+                // the next request waits until it is back
+                Err(e @ BoardError::Disconnected(_)) => {
+                    self.lost("a sensor read", &e);
+                    scan = true;
+                }
+                Err(e) => bail!("failed to read the sensors: {e}"),
+            }
+        }
+    }
+
+    /// Waits for the reed switches to change from `reading` and returns the
+    /// new reading. Boards that push their changes are listened to, others
+    /// are asked until something changes. If the board goes away, this waits
+    /// for it to come back and scans it.
+    pub async fn next_reading(&mut self, reading: Bitboard) -> anyhow::Result<Bitboard> {
+        if !self.connection.info().events {
+            return self.read_sensors(false).await;
+        }
+        match self.connection.next_event().await {
+            Ok(event) => {
+                debug!("{event:?}");
+                Ok(event.apply(reading))
+            }
+            Err(e @ BoardError::Disconnected(_)) => {
+                self.lost("waiting for sensor events", &e);
+                self.read_sensors(true).await
+            }
+            Err(e) => bail!("failed to wait for sensor events: {e}"),
+        }
+    }
+
+    /// Waits [`SETTLE_TIME`] for the reed switches to change from `reading`.
+    /// Returns `None` if they held still, or the new reading if they did not.
+    pub async fn settle(&mut self, reading: Bitboard) -> anyhow::Result<Option<Bitboard>> {
+        if !self.connection.info().events {
+            // a sensor read waits for the next change, so look again later
+            tokio::time::sleep(SETTLE_TIME).await;
+            let settled = self.read_sensors(true).await?;
+            return Ok((settled != reading).then_some(settled));
+        }
+        // events are only taken off the port once whole, so waiting for one
+        // can be given up on
+        let Ok(next) = tokio::time::timeout(SETTLE_TIME, self.next_reading(reading)).await else {
+            return Ok(None);
+        };
+        next.map(Some)
+    }
+
+    pub async fn show(&mut self, rgb: RGB) -> anyhow::Result<()> {
+        let frame = LedFrame::new(rgb_to_colours(rgb))?;
+        // the hints are only a help, so play on without them
+        match self.connection.set_leds(frame).await {
+            Ok(()) => Ok(()),
+            Err(BoardError::Timeout { attempts }) => {
+                warn!("LEDs did not answer after {attempts} attempts");
+                Ok(())
+            }
+            Err(e @ BoardError::Disconnected(_)) => {
+                self.lost("setting the LEDs", &e);
+                Ok(())
+            }
+            Err(e) => bail!("failed to set the LEDs: {e}"),
+        }
+    }
+
+    /// Moves the magnet through `steps`, returning `false` if the board did
+    /// not confirm the move, in which case it may or may not have happened.
+    /// Steps that do not fit in one instruction are sent in several.
+    pub async fn run_steps(&mut self, steps: &[Step]) -> anyhow::Result<bool> {
+        for steps in split_steps(steps, MAX_MAGNET_STEPS) {
+            match self.connection.run_steps(steps).await {
+                Ok(()) => {}
+                Err(BoardError::Timeout { attempts }) => {
+                    warn!("magnet move not confirmed after {attempts} attempts");
+                    return Ok(false);
+                }
+                Err(e @ BoardError::Disconnected(_)) => {
+                    self.lost("a magnet move", &e);
+                    return Ok(false);
+                }
+                Err(e) => bail!("failed to move the magnet: {e}"),
+            }
+        }
+        Ok(true)
+    }
+}
@@ -0,0 +1,317 @@
+#![cfg(unix)]
+
+extern crate board_simulator;
+
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+use board_simulator::pty::PtyBoard;
+use board_simulator::{Report, SensorEvent, SimulatedBoard};
+use flagfall_core::{move_to_steps, promotion_menu, Graveyard};
+use flagfall_protocol::MagnetStep;
+use shakmaty::{san::San, uci::Uci, CastlingMode, Chess, Color, Move, Position, Role};
+
+/// How long a whole game may take, including the board's handshake.
+const GAME_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// What a scripted game should leave behind on the simulated board.
+struct Expected {
+    position: Chess,
+    /// The user's sensor reads, one move per entry.
+    reads: Vec<Vec<Vec<SensorEvent>>>,
+    magnet_moves: Vec<Vec<MagnetStep>>,
+    /// Pieces the opponent captured, which end up parked off the board.
+    opponent_captures: usize,
+    uci: Vec<String>,
+}
+
+/// Finds a binary from another package of the workspace, built next to
+/// master-program.
+fn _sibling_exe(name: &str) -> PathBuf {
+    let exe = PathBuf::from(env!("CARGO_BIN_EXE_master-program"))
+        .with_file_name(format!("{name}{}", std::env::consts::EXE_SUFFIX));
+    assert!(
+        exe.exists(),
+        "{} is missing, build the workspace first",
+        exe.display()
+    );
+    exe
+}
+
+/// The SAN moves of `pgn`, skipping tags, move numbers and the result.
+fn _pgn_moves(pgn: &str) -> Vec<San> {
+    pgn.lines()
+        .filter(|line| !line.starts_with('['))
+        .flat_map(str::split_whitespace)
+        .map(|token| token.rsplit('.').next().unwrap_or_default())
+        .filter(|token| !token.is_empty() && !["1-0", "0-1", "1/2-1/2", "*"].contains(token))
+        .map(|token| token.parse().unwrap())
+        .collect()
+}
+
+/// The reed-switch changes of a user playing `mv`, one sensor read each.
+fn _sensor_reads(pos: &Chess, mv: &Move) -> Vec<Vec<SensorEvent>> {
+    use SensorEvent::{Lift, Place};
+
+    let reads = match *mv {
+        Move::Castle { king, rook } => {
+            let side = mv.castling_side().unwrap();
+            let turn = pos.turn();
+            vec![
+                Lift(king),
+                Place(side.king_to(turn)),
+                Lift(rook),
+                Place(side.rook_to(turn)),
+            ]
+        }
+        Move::EnPassant { from, to } => {
+            let captured = shakmaty::Square::from_coords(to.file(), from.rank());
+            vec![Lift(captured), Lift(from), Place(to)]
+        }
+        Move::Normal {
+            from,
+            capture,
+            to,
+            promotion,
+            ..
+        } => {
+            let mut reads = vec![Lift(from)];
+            if capture.is_some() {
+                reads.push(Lift(to));
+            }
+            // anything but a queen is chosen on the promotion menu first
+            if let Some(role) = promotion.filter(|&role| role != Role::Queen) {
+                let (menu, _) = promotion_menu(pos, from)
+                    .into_iter()
+                    .find(|&(_, option)| option == role)
+                    .unwrap();
+                reads.extend([Place(menu), Lift(menu)]);
+            }
+            reads.push(Place(to));
+            reads
+        }
+        Move::Put { .. } => unreachable!(),
+    };
+    reads.into_iter().map(|event| vec![event]).collect()
+}
+
+/// Works out what playing `pgn` as `user` against the stub should do.
+#[allow(clippy::cast_possible_truncation)]
+fn _expect(pgn: &str, user: Color) -> Expected {
+    let mut expected = Expected {
+        position: Chess::default(),
+        reads: Vec::new(),
+        magnet_moves: Vec::new(),
+        opponent_captures: 0,
+        uci: Vec::new(),
+    };
+    // kept the way master-program keeps it
+    let mut graveyard = Graveyard::new();
+    for san in _pgn_moves(pgn) {
+        let pos = &expected.position;
+        let mv = san.to_move(pos).unwrap();
+        expected
+            .uci
+            .push(Uci::from_move(&mv, CastlingMode::Standard).to_string());
+        if pos.turn() == user {
+            expected.reads.push(_sensor_reads(pos, &mv));
+            graveyard.capture(pos.board(), &mv);
+        } else {
+            let steps = move_to_steps(&mv, pos.board(), &graveyard);
+            expected.magnet_moves.push(
+                steps
+                    .iter()
+                    .map(|step| MagnetStep {
+                        x: step.x as f32,
+                        y: step.y as f32,
+                        magnet: step.magnet,
+                    })
+                    .collect(),
+            );
+            if mv.is_capture() {
+                expected.opponent_captures += 1;
+            }
+            graveyard.play(pos.board(), &mv);
+        }
+        expected.position = expected.position.clone().play(&mv).unwrap();
+    }
+    expected
+}
+
+/// Plays `pgn` through master-program and the opponent stub on a simulated
+/// board, with the user playing `user` and master-program given `args`.
+fn _play_game(pgn: &str, user: Color, args: &[&str]) -> (Expected, Report) {
+    let expected = _expect(pgn, user);
+    let report = _run_game(&expected, user, args);
+    (expected, report)
+}
+
+/// Plays the game of `expected` through master-program and the opponent stub
+/// on a simulated board, with the user's sensor reads as scripted there.
+fn _run_game(expected: &Expected, user: Color, args: &[&str]) -> Report {
+    let script = expected.reads.iter().flatten().cloned().collect();
+
+    let mut pty = PtyBoard::open().expect("[game_test::play_game] No pty");
+    let path = pty.path().unwrap();
+    let server = std::thread::spawn(move || {
+        let mut board = SimulatedBoard::new(Chess::default().board().occupied(), script);
+        pty.serve(&mut board).map(|()| board.report())
+    });
+
+    let mut master = Command::new(env!("CARGO_BIN_EXE_master-program"))
+        .arg("--opponent-wrapper")
+        .arg(_sibling_exe("opponent-stub"))
+        .args(args)
+        .env("FLAGFALL_SERIAL_PORT", &path)
+        .env("FLAGFALL_STUB_MOVES", expected.uci.join(" "))
+        .env("FLAGFALL_STUB_COLOUR", user.fold_wb("white", "black"))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("[game_test::play_game] Cannot start master-program");
+
+    let deadline = Instant::now() + GAME_TIMEOUT;
+    let status = loop {
+        if let Some(status) = master.try_wait().unwrap() {
+            break status;
+        }
+        if Instant::now() > deadline {
+            master.kill().unwrap();
+            panic!("[game_test::play_game] master-program did not finish the game");
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    };
+    assert!(status.success(), "master-program failed with {status}");
+
+    server.join().unwrap().unwrap()
+}
+
+fn _assert_game(expected: &Expected, report: &Report) {
+    assert_eq!(report.occupancy, expected.position.board().occupied().0);
+    assert_eq!(report.magnet_moves, expected.magnet_moves);
+    assert_eq!(report.parked.len(), expected.opponent_captures);
+    assert!(
+        report.collisions.is_empty(),
+        "pieces ran into each other at {:?}",
+        report.collisions
+    );
+
+    // one LED frame per sensor read: lit while a piece is up, dark once the
+    // move is complete, which may be straight away
+    let mut frames = report.led_frames.iter();
+    for reads in &expected.reads {
+        let move_frames = frames.by_ref().take(reads.len()).collect::<Vec<_>>();
+        assert_eq!(move_frames.len(), reads.len());
+        assert!(reads.len() == 1 || move_frames[0].iter().any(|&colour| colour != 0));
+        assert!(move_frames[reads.len() - 1]
+            .iter()
+            .all(|&colour| colour == 0));
+    }
+    assert!(frames.next().is_none());
+}
+
+#[test]
+fn test_scholars_mate_as_white() {
+    let (expected, report) = _play_game(
+        "1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0",
+        Color::White,
+        &[],
+    );
+    assert!(expected.position.is_checkmate());
+    _assert_game(&expected, &report);
+}
+
+#[test]
+fn test_captures_and_castling_as_black() {
+    let (expected, report) = _play_game(
+        "1. e4 d5 2. exd5 Nf6 3. Nc3 Nxd5 4. Nxd5 Qxd5 5. Nf3 Bg4 6. Be2 Nc6 7. O-O O-O-O *",
+        Color::Black,
+        &[],
+    );
+    assert_eq!(expected.opponent_captures, 2);
+    _assert_game(&expected, &report);
+}
+
+#[test]
+fn test_en_passant_as_white() {
+    let (expected, report) =
+        _play_game("1. e4 a6 2. e5 d5 3. exd6 cxd6 4. Qf3 *", Color::White, &[]);
+    assert_eq!(expected.opponent_captures, 1);
+    _assert_game(&expected, &report);
+}
+
+#[test]
+fn test_move_finished_while_restoring() {
+    use shakmaty::Square;
+    use SensorEvent::{Lift, Place};
+
+    // the last move, as the simulated player would not wait for the board to
+    // settle before the next one
+    let mut expected = _expect("1. e4 *", Color::White);
+    // a knight lifted by mistake and put back only once the pawn has moved,
+    // which the state machine cannot follow
+    expected.reads[0] = [
+        Lift(Square::G1),
+        Lift(Square::E2),
+        Place(Square::E4),
+        Place(Square::G1),
+    ]
+    .into_iter()
+    .map(|event| vec![event])
+    .collect();
+    let report = _run_game(&expected, Color::White, &[]);
+    _assert_game(&expected, &report);
+}
+
+#[test]
+fn test_no_move_passed_through_while_restoring() {
+    use shakmaty::Square;
+    use SensorEvent::{Lift, Place};
+
+    let mut expected = _expect("1. e4 e5 2. Nf3 *", Color::White);
+    // both knights lifted by mistake, and on the way back to where they
+    // were the board reads as if Nf3 had been played
+    expected.reads[0] = [
+        Lift(Square::G1),
+        Lift(Square::B1),
+        Place(Square::F3),
+        Place(Square::B1),
+        Lift(Square::F3),
+        Place(Square::G1),
+        Lift(Square::E2),
+        Place(Square::E4),
+    ]
+    .into_iter()
+    .map(|event| vec![event])
+    .collect();
+    let report = _run_game(&expected, Color::White, &[]);
+    _assert_game(&expected, &report);
+}
+
+#[test]
+fn test_capture_in_one_motion() {
+    use shakmaty::Square;
+    use SensorEvent::Lift;
+
+    let mut expected = _expect("1. e4 d5 2. exd5 *", Color::White);
+    // the pawn on d5 swapped out under the capturing pawn, so its reed
+    // switch never opens
+    expected.reads[1] = vec![vec![Lift(Square::E4)]];
+    let report = _run_game(&expected, Color::White, &[]);
+    _assert_game(&expected, &report);
+}
+
+#[test]
+fn test_reset_after_the_game() {
+    let (expected, report) = _play_game("1. e4 d5 2. exd5 Nf6 *", Color::Black, &["--reset"]);
+    assert_eq!(report.occupancy, Chess::default().board().occupied().0);
+    assert!(report.parked.is_empty());
+    assert!(report.collisions.is_empty());
+    assert_eq!(
+        report.magnet_moves[..expected.magnet_moves.len()],
+        expected.magnet_moves
+    );
+    assert!(report.magnet_moves.len() > expected.magnet_moves.len());
+}
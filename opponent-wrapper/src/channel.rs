@@ -0,0 +1,48 @@
+//! The wrapper's end of the protocol spoken with the master program, over
+//! stdin and stdout. Logging goes to stderr so it never mixes with messages.
+
+use flagfall_protocol::{
+    read_message, write_message, OpponentMessage, PlayerMessage, ProtocolError,
+};
+use log::{error, info, warn};
+
+pub fn send(message: &OpponentMessage) {
+    if let Err(e) = write_message(&mut std::io::stdout().lock(), message) {
+        error!("failed to send {message:?}: {e}");
+    }
+}
+
+/// Waits for the next message from the master program, skipping anything
+/// that is not one. Returns `None` once stdin is closed.
+pub fn recv() -> Option<PlayerMessage> {
+    loop {
+        let read = read_message(&mut std::io::stdin().lock());
+        match read {
+            Ok(message) => return message,
+            Err(e @ ProtocolError::Malformed { .. }) => warn!("ignoring {e}"),
+            Err(e) => {
+                error!("{e}");
+                return None;
+            }
+        }
+    }
+}
+
+/// Asks the user `text` through the master program and returns their answer.
+///
+/// Exits the wrapper if the master program quits instead of answering.
+pub fn ask(text: &str) -> String {
+    send(&OpponentMessage::Prompt {
+        text: text.to_string(),
+    });
+    loop {
+        match recv() {
+            Some(PlayerMessage::Answer { text }) => return text.trim().to_string(),
+            Some(PlayerMessage::Quit) | None => {
+                info!("master program quit during setup, exiting");
+                std::process::exit(0);
+            }
+            Some(message) => warn!("ignoring {message:?} while waiting for an answer"),
+        }
+    }
+}
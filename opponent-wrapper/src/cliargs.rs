@@ -1,4 +1,5 @@
 use clap::Parser;
+use shakmaty::fen::Fen;
 
 #[derive(Parser)]
 #[clap(author, version, about)]
@@ -9,4 +10,10 @@ pub struct Cli {
     pub lichess: bool,
     #[clap(short, long)]
     pub engine: bool,
+    /// Play Chess960 from the given starting position number (0-959).
+    #[clap(long, value_name = "INDEX")]
+    pub chess960: Option<u32>,
+    /// Start from the given position instead.
+    #[clap(long, value_name = "FEN", conflicts_with = "chess960")]
+    pub fen: Option<Fen>,
 }
\ No newline at end of file
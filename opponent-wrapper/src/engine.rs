@@ -1,20 +1,31 @@
 
 use std::io::{Write, BufRead, BufReader};
 
-use log::{info, error};
+use log::{error, info, warn};
 
-use shakmaty::{Position, fen::Fen, san::San};
+use flagfall_core::variant::StartPosition;
+use flagfall_protocol::{Colour, OpponentMessage, PlayerMessage};
+use shakmaty::{fen::Fen, san::San, uci::Uci, Chess, Outcome, Position};
 
-use crate::{gametype::VsComputer, user::{self, ChallengeColour}, VIRIDITHAS_EXECUTABLE_PATH, MAIA_EXECUTABLE_PATH};
+use crate::{
+    channel,
+    gametype::VsComputer,
+    user::{self, ChallengeColour},
+    MAIA_EXECUTABLE_PATH, VIRIDITHAS_EXECUTABLE_PATH,
+};
 
-pub fn main() {
-    let (uname, schema) = user::get_challenge_schema::<VsComputer>();
+#[allow(clippy::too_many_lines)]
+pub fn main(start: &StartPosition) {
+    let (uname, schema) = user::get_challenge_schema::<VsComputer>(start);
 
     let executable_path = match uname.as_str() {
         "v" | "viridithas" => VIRIDITHAS_EXECUTABLE_PATH,
         "m" | "maia" => MAIA_EXECUTABLE_PATH,
         _ => {
             error!("invalid username for computer opponent: {uname}");
+            channel::send(&OpponentMessage::Error {
+                message: format!("invalid computer opponent: {uname}"),
+            });
             return;
         }
     };
@@ -23,6 +34,9 @@ pub fn main() {
 
     if !std::path::Path::new(executable_path).exists() {
         error!("engine executable not found at: {executable_path}");
+        channel::send(&OpponentMessage::Error {
+            message: format!("engine executable not found at: {executable_path}"),
+        });
         return;
     }
 
@@ -32,8 +46,12 @@ pub fn main() {
         .stdout(std::process::Stdio::piped())
         .spawn()
         .expect("Failed to start engine");
-    
-    let mut game_state = shakmaty::Chess::default();
+
+    let mut game_state = start.to_position().unwrap();
+    if start.castling_mode() == shakmaty::CastlingMode::Chess960 {
+        info!("sending command: setoption name UCI_Chess960 value true");
+        send_line(&mut engine, "setoption name UCI_Chess960 value true\n");
+    }
     let human_turn = match schema.color {
         ChallengeColour::White => shakmaty::Color::White,
         ChallengeColour::Black => shakmaty::Color::Black,
@@ -45,29 +63,40 @@ pub fn main() {
             }
         }
     };
+    channel::send(&OpponentMessage::GameStart {
+        colour: human_turn.fold_wb(Colour::White, Colour::Black),
+    });
 
     'game_loop: loop {
-        if game_state.is_game_over() {
+        if let Some(outcome) = game_state.outcome() {
+            channel::send(&game_end(&game_state, outcome));
             send_line(&mut engine, "quit");
             break;
         }
         if human_turn == game_state.turn() {
-            let mut buf = String::new();
             let mut validation_failures = 0;
             let mv = loop {
                 if validation_failures > 3 {
                     error!("too many validation failures, aborting game");
-                    return;
-                }
-                buf.clear();
-                std::io::stdin().read_line(&mut buf).unwrap();
-                let line = buf.trim();
-                if matches!(line, "\x04" | "-1" | "quit" | "exit") {
-                    info!("received quit signal, sending \"quit\" to engine");
+                    channel::send(&OpponentMessage::Error {
+                        message: "too many invalid moves, aborting game".to_string(),
+                    });
                     send_line(&mut engine, "quit");
                     break 'game_loop;
                 }
-                let Ok(uci) = line.parse::<shakmaty::uci::Uci>() else {
+                let line = match channel::recv() {
+                    Some(PlayerMessage::Move { uci }) => uci,
+                    Some(PlayerMessage::Quit) | None => {
+                        info!("received quit signal, sending \"quit\" to engine");
+                        send_line(&mut engine, "quit");
+                        break 'game_loop;
+                    }
+                    Some(message) => {
+                        warn!("ignoring {message:?} while waiting for a move");
+                        continue;
+                    }
+                };
+                let Ok(uci) = line.parse::<Uci>() else {
                     error!("invalid UCI format: \"{line}\"");
                     validation_failures += 1;
                     continue;
@@ -83,11 +112,14 @@ pub fn main() {
         } else {
             let mvstr = get_engine_move(&mut engine, &Fen::from_position(game_state.clone(), shakmaty::EnPassantMode::Legal).to_string(), 1000);
             info!("engine move: {mvstr}");
-            let uci = mvstr.parse::<shakmaty::uci::Uci>().unwrap();
+            let uci = mvstr.parse::<Uci>().unwrap();
             let mv = uci.to_move(&game_state).unwrap();
             let san = San::from_move(&game_state, &mv);
             game_state.play_unchecked(&mv);
-            println!("{san}");
+            channel::send(&OpponentMessage::Move {
+                uci: mvstr,
+                san: san.to_string(),
+            });
         }
     }
 
@@ -100,6 +132,25 @@ pub fn main() {
     info!("Engine exited with status: {status}");
 }
 
+/// Describes how the game ended in `position`.
+fn game_end(position: &Chess, outcome: Outcome) -> OpponentMessage {
+    let reason = if position.is_checkmate() {
+        "checkmate"
+    } else if position.is_stalemate() {
+        "stalemate"
+    } else if position.is_insufficient_material() {
+        "insufficient material"
+    } else {
+        "draw"
+    };
+    OpponentMessage::GameEnd {
+        winner: outcome
+            .winner()
+            .map(|winner| winner.fold_wb(Colour::White, Colour::Black)),
+        reason: reason.to_string(),
+    }
+}
+
 fn send_line(process: &mut std::process::Child, line: &str) {
     process
         .stdin
@@ -2,7 +2,14 @@
 use futures_util::StreamExt;
 use log::{debug, error, info, warn};
 
-use crate::{user::{ChallengeSchema, self}, gametype::VsHuman};
+use flagfall_core::variant::StartPosition;
+use flagfall_protocol::{Colour, OpponentMessage, PlayerMessage};
+
+use crate::{
+    channel,
+    gametype::VsHuman,
+    user::{self, ChallengeSchema},
+};
 
 use super::{LICHESS_HOST, LICHESS_TOKEN};
 
@@ -10,16 +17,20 @@ use reqwest::Client;
 use reqwest::Response;
 use shakmaty::Move;
 
-use std::io::Write;
-use shakmaty::{san::San, uci::Uci, Position};
+use shakmaty::{fen::Fen, san::San, uci::Uci, CastlingMode, Position};
 
 use shakmaty::Chess;
 
-pub async fn send_move_to_game(client: &Client, game_id: &str, mv: &Move) -> Response {
+pub async fn send_move_to_game(
+    client: &Client,
+    game_id: &str,
+    mv: &Move,
+    castling_mode: CastlingMode,
+) -> Response {
     client
         .post(format!(
             "{LICHESS_HOST}/api/board/game/{game_id}/move/{}",
-            mv.to_uci(shakmaty::CastlingMode::Standard)
+            mv.to_uci(castling_mode)
         ))
         .bearer_auth(LICHESS_TOKEN)
         .send()
@@ -40,8 +51,8 @@ pub async fn send_challenge(client: &Client, username: &str, schema: &ChallengeS
         .unwrap()
 }
 
-pub async fn create_new_game(client: &Client) -> Option<String> {
-    let (username, schema) = user::get_challenge_schema::<VsHuman>();
+pub async fn create_new_game(client: &Client, start: &StartPosition) -> Option<String> {
+    let (username, schema) = user::get_challenge_schema::<VsHuman>(start);
     let response = send_challenge(client, &username, &schema).await;
     debug!("challenge sent, response: {response:?}");
     let mut stream = response.bytes_stream();
@@ -71,22 +82,16 @@ pub async fn join_game(
     client: &Client,
     n_current_games: usize,
     default_game: &serde_json::Value,
+    start: &StartPosition,
 ) -> Option<String> {
-    println!("create a new game or join an existing one? [C|J] (you have {n_current_games} ongoing game{})", if n_current_games == 1 { "" } else { "s" });
-    let mut user_input = String::new();
-    std::io::stdin().read_line(&mut user_input).unwrap();
-    let user_input = user_input.trim().to_lowercase();
+    let user_input = channel::ask(&format!("create a new game or join an existing one? [C|J] (you have {n_current_games} ongoing game{})", if n_current_games == 1 { "" } else { "s" })).to_lowercase();
     if user_input == "c" {
         loop {
-            let game_id = create_new_game(client).await;
+            let game_id = create_new_game(client, start).await;
             if let Some(game_id) = game_id {
                 return Some(game_id);
             }
-            println!("error creating game, try again? [Y|N]");
-            let mut user_input = String::new();
-            std::io::stdin().read_line(&mut user_input).unwrap();
-            let user_input = user_input.trim().to_lowercase();
-            if user_input != "y" {
+            if channel::ask("error creating game, try again? [Y|N]").to_lowercase() != "y" {
                 return None;
             }
         }
@@ -102,16 +107,30 @@ pub async fn join_game(
             error!("no 'gameId' field in json string ({json}), exiting.", json = default_game);
             return None;
         };
-        return Some(game_id.to_string());
+        Some(game_id.to_string())
     } else {
         error!("invalid input, exiting.");
-        return None;
+        None
+    }
+}
+
+/// Reads the starting position of a game from its `gameFull` event.
+fn initial_position(game: &serde_json::Value) -> (Chess, CastlingMode) {
+    let fen = match game["initialFen"].as_str() {
+        None | Some("startpos") => Fen::default(),
+        Some(fen) => fen.parse::<Fen>().unwrap(),
     };
+    let castling_mode = if game["variant"]["key"].as_str() == Some("chess960") {
+        CastlingMode::Chess960
+    } else {
+        CastlingMode::detect(fen.as_setup())
+    };
+    (fen.into_position(castling_mode).unwrap(), castling_mode)
 }
 
 
 #[allow(clippy::too_many_lines, clippy::cognitive_complexity)]
-pub async fn main() {
+pub async fn main(start: &StartPosition) {
     info!("creating reqwest client");
     let client = Client::builder()
         .user_agent("flagfall-lichess-api")
@@ -138,7 +157,7 @@ pub async fn main() {
 
     let n_current_games = current_games.as_array().unwrap().len();
     info!("number of currently active games: {n_current_games}");
-    let game_id = join_game(&client, n_current_games, &current_games[0])
+    let game_id = join_game(&client, n_current_games, &current_games[0], start)
         .await
         .unwrap();
 
@@ -156,6 +175,8 @@ pub async fn main() {
 
     let game = stream.next().await.unwrap().unwrap();
     let game: serde_json::Value = serde_json::from_slice(&game).unwrap();
+    let (initial_board, castling_mode) = initial_position(&game);
+    info!("starting position: {fen}", fen = initial_board.board());
 
     // get the color
     let colour = game["color"].as_str().unwrap();
@@ -171,6 +192,13 @@ pub async fn main() {
         }
     };
     info!("colour: {colour}");
+    channel::send(&OpponentMessage::GameStart {
+        colour: if colour == "white" {
+            Colour::White
+        } else {
+            Colour::Black
+        },
+    });
 
     info!("entering game stream loop");
     let mut stm = colour;
@@ -194,6 +222,27 @@ pub async fn main() {
             continue;
         }
 
+        // clocks and status live in the nested state of the first event
+        let state = v.get("state").unwrap_or(&v);
+        if let (Some(white_ms), Some(black_ms)) = (state["wtime"].as_u64(), state["btime"].as_u64())
+        {
+            channel::send(&OpponentMessage::Clock { white_ms, black_ms });
+        }
+        if let Some(status) = state["status"].as_str() {
+            if !matches!(status, "created" | "started") {
+                info!("game over: {status}");
+                channel::send(&OpponentMessage::GameEnd {
+                    winner: match state["winner"].as_str() {
+                        Some("white") => Some(Colour::White),
+                        Some("black") => Some(Colour::Black),
+                        _ => None,
+                    },
+                    reason: status.to_string(),
+                });
+                return;
+            }
+        }
+
         // continue if we're not to move:
         if stm != colour {
             stm = match stm {
@@ -215,37 +264,49 @@ pub async fn main() {
         };
 
         info!("moves made so far: {moves}");
-        let mut board = Chess::default();
-        let moves = moves.split_whitespace().collect::<Vec<_>>();
-        for mv in &moves {
-            let mv: Uci = mv.parse().unwrap();
-            let mv = mv.to_move(&board).unwrap();
+        let mut board = initial_board.clone();
+        let mut last_move = None;
+        for uci in moves.split_whitespace() {
+            let mv = uci.parse::<Uci>().unwrap().to_move(&board).unwrap();
+            last_move = Some((uci.to_string(), San::from_move(&board, &mv).to_string()));
             board = board.play(&mv).unwrap();
         }
 
-        println!("opponent's move: {}", moves.last().unwrap());
-
-        // print the legal moves:
-        let legal_moves = board.legal_moves();
-
-        print!("legal moves: ");
-        for m in &legal_moves {
-            print!("{}, ", San::from_move(&board, m));
+        if let Some((uci, san)) = last_move {
+            info!("opponent's move: {san}");
+            channel::send(&OpponentMessage::Move { uci, san });
         }
-        println!();
 
-        let mut user_input = String::new();
-        print!("enter move: ");
-        std::io::stdout().flush().unwrap();
-        std::io::stdin().read_line(&mut user_input).unwrap();
-        let user_input = user_input.trim();
-
-        let user_move = user_input.parse::<San>().unwrap().to_move(&board).unwrap();
+        let legal_moves = board.legal_moves();
+        debug!(
+            "legal moves: {}",
+            legal_moves
+                .iter()
+                .map(|m| San::from_move(&board, m).to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+
+        let user_move = loop {
+            match channel::recv() {
+                Some(PlayerMessage::Move { uci }) => {
+                    if let Some(mv) = uci.parse::<Uci>().ok().and_then(|u| u.to_move(&board).ok()) {
+                        break mv;
+                    }
+                    error!("illegal UCI move: \"{uci}\"");
+                }
+                Some(PlayerMessage::Quit) | None => {
+                    info!("received quit signal, leaving the game stream");
+                    return;
+                }
+                Some(message) => warn!("ignoring {message:?} while waiting for a move"),
+            }
+        };
 
         // post the move in the form of a json string
         // like this: https://lichess.org/api/board/game/{gameId}/move/{move}
 
-        let res = send_move_to_game(&client, &game_id, &user_move).await;
+        let res = send_move_to_game(&client, &game_id, &user_move, castling_mode).await;
 
         let body = res.text().await.unwrap();
 
@@ -1,5 +1,8 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery)]
 
+use flagfall_core::variant::StartPosition;
+
+mod channel;
 mod cliargs;
 mod lichess;
 mod engine;
@@ -28,13 +31,21 @@ async fn main() {
         log::set_max_level(log::LevelFilter::Debug);
     }
 
+    let start = match (args.chess960, args.fen) {
+        (Some(index), _) => StartPosition::Chess960(index),
+        (None, Some(fen)) => StartPosition::Fen(fen),
+        (None, None) => StartPosition::Standard,
+    };
+    if let Err(e) = start.to_position() {
+        log::error!("cannot start a game: {e}");
+        return;
+    }
+
     if args.lichess {
-        lichess::main().await;
+        lichess::main(&start).await;
     }
 
     if args.engine {
-        engine::main();
+        engine::main(&start);
     }
-
-    print!("\x04");
 }
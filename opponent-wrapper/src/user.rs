@@ -1,10 +1,12 @@
 
 use std::str::FromStr;
 
-use log::error;
+use flagfall_core::variant::StartPosition;
+use log::{error, warn};
 use serde::Serialize;
+use shakmaty::{fen::Fen, EnPassantMode};
 
-use crate::gametype::GameType;
+use crate::{channel, gametype::GameType};
 
 #[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize)]
 pub enum ChallengeColour {
@@ -44,28 +46,28 @@ pub struct ChallengeSchema {
     pub keep_alive_stream: bool,
 }
 
-pub fn get_challenge_schema<T: GameType>() -> (String, ChallengeSchema) {
-    let mut user_input = String::new();
-    if T::IS_VS_HUMAN {
-        println!("enter the username to challenge:");
+/// Asks the user for the challenge parameters, returning the opponent's name
+/// and the challenge to send.
+///
+/// Unless `start` is the standard starting position, the challenge carries its
+/// FEN and cannot be rated.
+pub fn get_challenge_schema<T: GameType>(start: &StartPosition) -> (String, ChallengeSchema) {
+    let username = if T::IS_VS_HUMAN {
+        channel::ask("enter the username to challenge:")
     } else {
-        println!("do you want to challenge Viridithas or Maia? [V|M]");
-    };
-    std::io::stdin().read_line(&mut user_input).unwrap();
-    let username = user_input.trim().to_lowercase();
+        channel::ask("do you want to challenge Viridithas or Maia? [V|M]")
+    }
+    .to_lowercase();
     let time_control = if T::IS_VS_HUMAN {
-        println!("enter the time control in the form of 'min+inc' (e.g. '5+2' for 5 minutes + 2 seconds increment):");
-        user_input.clear();
-        std::io::stdin().read_line(&mut user_input).unwrap();
-        user_input.trim().to_lowercase()
+        channel::ask("enter the time control in the form of 'min+inc' (e.g. '5+2' for 5 minutes + 2 seconds increment):").to_lowercase()
     } else {
         "15+10".to_string()
     };
-    let rated = if T::IS_VS_HUMAN {
-        println!("should the game be rated? [Y|N]");
-        user_input.clear();
-        std::io::stdin().read_line(&mut user_input).unwrap();
-        match user_input.trim().to_lowercase().as_str() {
+    let mut rated = if T::IS_VS_HUMAN {
+        match channel::ask("should the game be rated? [Y|N]")
+            .to_lowercase()
+            .as_str()
+        {
             "y" => true,
             "n" => false,
             _ => panic!("invalid input"),
@@ -73,10 +75,7 @@ pub fn get_challenge_schema<T: GameType>() -> (String, ChallengeSchema) {
     } else {
         false
     };
-    println!("enter the challenge colour [white|black|random]:");
-    user_input.clear();
-    std::io::stdin().read_line(&mut user_input).unwrap();
-    let colour = user_input.trim().to_lowercase();
+    let colour = channel::ask("enter the challenge colour [white|black|random]:").to_lowercase();
     let Ok(colour) = colour.parse::<ChallengeColour>() else {
         error!("invalid colour: {colour}");
         panic!("invalid colour");
@@ -90,11 +89,25 @@ pub fn get_challenge_schema<T: GameType>() -> (String, ChallengeSchema) {
         * 60;
     let clock_increment = time_control
         .split('+')
-        .last()
+        .next_back()
         .unwrap()
         .parse::<u32>()
         .unwrap();
     let keep_alive_stream = true;
+    let fen = (*start != StartPosition::Standard).then(|| {
+        let pos = start.to_position().unwrap();
+        Fen::from_position(pos, EnPassantMode::Legal).to_string()
+    });
+    let variant = match start {
+        StartPosition::Standard => "standard",
+        StartPosition::Chess960(_) => "chess960",
+        StartPosition::Fen(_) => "fromPosition",
+    }
+    .to_string();
+    if rated && fen.is_some() {
+        warn!("games from a set starting position cannot be rated, sending a casual challenge");
+        rated = false;
+    }
     (
         username,
         ChallengeSchema {
@@ -102,8 +115,8 @@ pub fn get_challenge_schema<T: GameType>() -> (String, ChallengeSchema) {
             clock_limit,
             clock_increment,
             color: colour,
-            variant: "standard".to_string(),
-            fen: None,
+            variant,
+            fen,
             keep_alive_stream,
         },
     )
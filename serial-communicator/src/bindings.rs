@@ -3,9 +3,20 @@
 pub const SENSOR: u8 = 1;
 pub const MAGNET: u8 = 2;
 pub const LED: u8 = 3;
+pub const SCAN: u8 = 4;
+pub const SUBSCRIBE: u8 = 5;
+pub const EVENT: u8 = 6;
 pub const HANDSHAKE: u8 = 16;
 pub const ACK: u8 = 32;
+pub const NAK: u8 = 33;
 pub const QUIT: u8 = 255;
+pub const FRAME_START: u8 = 126;
+pub const FRAME_MAX_PAYLOAD: u16 = 504;
+pub const FIRMWARE_PROTOCOL_VERSION: u8 = 1;
+pub const CAP_SENSORS: u8 = 1;
+pub const CAP_MAGNET: u8 = 2;
+pub const CAP_LEDS: u8 = 4;
+pub const CAP_EVENTS: u8 = 8;
 #[repr(u32)]
 #[non_exhaustive]
 #[doc = " @brief\n Enumerates the variants of operations to be worked by the arduino main program."]
@@ -14,6 +25,9 @@ pub enum OpKind {
     Sensor = 0,
     Magnet = 1,
     Led = 2,
-    Noop = 3,
-    Quit = 4,
+    Scan = 3,
+    Handshake = 4,
+    Subscribe = 5,
+    Noop = 6,
+    Quit = 7,
 }
@@ -0,0 +1,13 @@
+use clap::Parser;
+
+use serial_communicator::options::ConnectionArgs;
+
+#[derive(Parser)]
+#[clap(author, version, about)]
+pub struct Cli {
+    #[clap(flatten)]
+    pub connection: ConnectionArgs,
+    /// List the serial ports, marking the ones that would be used, and quit.
+    #[clap(long)]
+    pub list_ports: bool,
+}
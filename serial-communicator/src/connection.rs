@@ -0,0 +1,428 @@
+//! A client for the board, to drive it in-process rather than through the
+//! `serial-communicator` binary.
+//!
+//! A [`BoardConnection`] finds the Arduino, waits for its handshake and sends
+//! it requests with retries. If the Arduino goes away, the request fails with
+//! [`BoardError::Disconnected`] and the next one waits until it is back.
+//!
+//! Boards that push their reed switch changes can be watched through
+//! [`BoardConnection::events`] instead of asking with `SENSOR` over and over.
+
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::io;
+use std::time::Duration;
+
+use flagfall_core::Step;
+use flagfall_protocol::{BoardReply, BoardRequest, MagnetStep, LED_COUNT};
+use futures_util::{stream, Stream};
+use log::{error, info, warn};
+use shakmaty::Bitboard;
+use tokio::io::AsyncReadExt;
+use tokio_serial::SerialStream;
+
+use crate::bindings;
+use crate::device::DeviceSelector;
+use crate::events::SwitchEvent;
+use crate::frame::{Frame, FrameDecoder};
+use crate::handshake::{handshake, BoardInfo, HandshakeError};
+use crate::retry::{exchange_with, ExchangeError, RetryPolicy};
+use crate::{LedFrame, Request, RequestConversionError};
+
+/// How often to look for the Arduino again after it has gone away.
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Which board to connect to and how patiently.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConnectionOptions {
+    pub selector: DeviceSelector,
+    pub policy: RetryPolicy,
+    /// How long the firmware may take to answer the handshake after the port
+    /// is opened, which includes restarting and calibrating.
+    pub ready_timeout: Duration,
+}
+
+impl Default for ConnectionOptions {
+    fn default() -> Self {
+        Self {
+            selector: DeviceSelector::default(),
+            policy: RetryPolicy::default(),
+            ready_timeout: Duration::from_secs(30),
+        }
+    }
+}
+
+/// Why a request to the board failed.
+#[derive(Debug)]
+pub enum BoardError {
+    /// No matching device was found when connecting.
+    NotFound,
+    /// The firmware cannot be used. Nothing more can be sent.
+    Handshake(HandshakeError),
+    /// The request is not valid and was not sent.
+    Invalid(RequestConversionError),
+    /// The board cannot carry out the request, so it was not sent.
+    Unsupported(&'static str),
+    /// The board did not answer in time, even after `attempts` tries. The
+    /// request may still have been carried out, such as a magnet move whose
+    /// acknowledgement was lost.
+    Timeout { attempts: u32 },
+    /// The board answered `NAK` to all `attempts` tries.
+    Rejected { attempts: u32 },
+    /// The board went away, and the request was not carried out or only
+    /// partly. The next request waits until it is back.
+    Disconnected(io::Error),
+    /// The board answered with something that makes no sense.
+    UnexpectedReply(Frame),
+}
+
+impl Display for BoardError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "no board found"),
+            Self::Handshake(e) => write!(f, "cannot use board: {e}"),
+            Self::Invalid(e) => write!(f, "invalid request: {e}"),
+            Self::Unsupported(reason) => write!(f, "not supported: {reason}"),
+            Self::Timeout { attempts } => write!(f, "no reply after {attempts} attempts"),
+            Self::Rejected { attempts } => write!(f, "rejected {attempts} times"),
+            Self::Disconnected(e) => write!(f, "board disconnected: {e}"),
+            Self::UnexpectedReply(frame) => write!(f, "unexpected reply: {frame:x?}"),
+        }
+    }
+}
+
+impl std::error::Error for BoardError {}
+
+impl From<RequestConversionError> for BoardError {
+    fn from(e: RequestConversionError) -> Self {
+        Self::Invalid(e)
+    }
+}
+
+/// A connection to the board's Arduino.
+pub struct BoardConnection {
+    options: ConnectionOptions,
+    /// `None` while the Arduino is gone, until the next request needs it.
+    port: Option<SerialStream>,
+    info: BoardInfo,
+    /// Bytes read off `port` that are not a whole frame yet.
+    decoder: FrameDecoder,
+    /// `EVENT` frames that arrived while waiting for an answer.
+    events: VecDeque<Frame>,
+    /// Whether the firmware has been asked for events since it connected.
+    subscribed: bool,
+}
+
+impl BoardConnection {
+    /// Connects to the board and waits until its firmware is ready.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BoardError::NotFound`] if there is no board, or
+    /// [`BoardError::Handshake`] if its firmware cannot be used.
+    pub async fn open(options: ConnectionOptions) -> Result<Self, BoardError> {
+        let (port, info) = connect(&options).await?.ok_or(BoardError::NotFound)?;
+        Ok(Self {
+            options,
+            port: Some(port),
+            info,
+            decoder: FrameDecoder::default(),
+            events: VecDeque::new(),
+            subscribed: false,
+        })
+    }
+
+    /// What the firmware reported when last connecting.
+    #[must_use]
+    pub const fn info(&self) -> BoardInfo {
+        self.info
+    }
+
+    /// Waits for the reed switches to change, then reads them.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BoardError`] if the board does not give a reading.
+    pub async fn read_sensors(&mut self) -> Result<Bitboard, BoardError> {
+        let reply = self.send(&Request::Sensor).await?;
+        occupancy(reply)
+    }
+
+    /// Reads the reed switches once they have settled.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BoardError`] if the board does not give a reading.
+    pub async fn scan(&mut self) -> Result<Bitboard, BoardError> {
+        let reply = self.send(&Request::Scan).await?;
+        occupancy(reply)
+    }
+
+    /// Shows `frame` on the LEDs. Boards without a full set of LEDs are left
+    /// dark, as the hints are only a help.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BoardError`] if the LEDs were not confirmed to be set.
+    pub async fn set_leds(&mut self, frame: LedFrame) -> Result<(), BoardError> {
+        self.send(&Request::Led(frame)).await?;
+        Ok(())
+    }
+
+    /// Moves the magnet carriage through `steps`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BoardError`] if the move was not confirmed, in which case it
+    /// may or may not have happened.
+    #[allow(clippy::cast_possible_truncation)]
+    pub async fn run_steps(&mut self, steps: &[Step]) -> Result<(), BoardError> {
+        let steps = steps
+            .iter()
+            .map(|step| MagnetStep {
+                x: step.x as f32,
+                y: step.y as f32,
+                magnet: step.magnet,
+            })
+            .collect();
+        self.send(&Request::try_from(&BoardRequest::Magnet { steps })?)
+            .await?;
+        Ok(())
+    }
+
+    /// Waits for the next reed switch change, subscribing to the firmware's
+    /// events first if need be. Changes made by the magnet carriage, or
+    /// already seen by [`Self::read_sensors`] or [`Self::scan`], are left out.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BoardError::Unsupported`] if the board does not push events,
+    /// or [`BoardError::Disconnected`] if it went away, after which events
+    /// may have been missed.
+    pub async fn next_event(&mut self) -> Result<SwitchEvent, BoardError> {
+        const _FN_NAME: &str = "[serial-communicator::connection::next_event]";
+
+        loop {
+            if let Some(frame) = self.events.pop_front() {
+                let event = SwitchEvent::from_payload(&frame.payload)
+                    .ok_or(BoardError::UnexpectedReply(frame))?;
+                info!("{_FN_NAME} Received {event:?}");
+                return Ok(event);
+            }
+            if !self.subscribed {
+                // => Reconnects first if needed, which starts a new subscription
+                self.send(&Request::Subscribe { enabled: true }).await?;
+                self.subscribed = true;
+                continue;
+            }
+            let Some(port) = &mut self.port else {
+                unreachable!("{_FN_NAME} Unsubscribed when the port was lost");
+            };
+
+            while let Some(decoded) = self.decoder.next_frame() {
+                match decoded {
+                    Ok(frame) if frame.opcode == bindings::EVENT => self.events.push_back(frame),
+                    Ok(frame) => warn!("{_FN_NAME} Skipping unexpected {frame:x?}"),
+                    Err(e) => warn!("{_FN_NAME} Dropping a corrupt frame: {e}"),
+                }
+            }
+            if !self.events.is_empty() {
+                continue;
+            }
+            let mut read_buf = [0_u8; 64];
+            match port.read(&mut read_buf).await {
+                Ok(0) => return Err(self.lost(io::ErrorKind::UnexpectedEof.into())),
+                Ok(read) => self.decoder.push(&read_buf[..read]),
+                Err(e) => return Err(self.lost(e)),
+            }
+        }
+    }
+
+    /// The reed switch changes as they happen, as by [`Self::next_event`].
+    ///
+    /// The stream never ends. After [`BoardError::Disconnected`] the next
+    /// item waits for the board to come back, while other errors are likely
+    /// to repeat.
+    pub fn events(&mut self) -> impl Stream<Item = Result<SwitchEvent, BoardError>> + '_ {
+        stream::unfold(self, |connection| async move {
+            let event = connection.next_event().await;
+            Some((event, connection))
+        })
+    }
+
+    /// Carries out `request` for the `serial-communicator` protocol. `Quit` is
+    /// left to the caller.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BoardError::Handshake`] once the board cannot be used any
+    /// more. Every other failure is a reply.
+    pub async fn request(&mut self, request: &BoardRequest) -> Result<BoardReply, BoardError> {
+        let result = match Request::try_from(request) {
+            Ok(request) => self.send(&request).await,
+            Err(e) => Err(e.into()),
+        };
+        let reply = match (request, result) {
+            (BoardRequest::Sense | BoardRequest::Scan, Ok(frame)) => match occupancy(frame) {
+                Ok(occupancy) => BoardReply::Sensors {
+                    occupancy: occupancy.0,
+                },
+                Err(e) => BoardReply::Error {
+                    message: e.to_string(),
+                },
+            },
+            (_, Ok(_)) => BoardReply::Done,
+            (_, Err(BoardError::Timeout { attempts })) => BoardReply::Timeout { attempts },
+            (_, Err(BoardError::Disconnected(e))) => BoardReply::Disconnected {
+                message: e.to_string(),
+            },
+            (_, Err(e @ BoardError::Handshake(_))) => return Err(e),
+            // => Leave it to the master program whether the game can go on
+            (_, Err(e)) => BoardReply::Error {
+                message: e.to_string(),
+            },
+        };
+        Ok(reply)
+    }
+
+    /// Sends `request` to the Arduino, reconnecting first if it went away,
+    /// and returns its answer.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BoardError`] if there is no usable answer.
+    pub async fn send(&mut self, request: &Request) -> Result<Frame, BoardError> {
+        const _FN_NAME: &str = "[serial-communicator::connection::send]";
+
+        if self.port.is_none() {
+            let (port, info) = reconnect(&self.options).await?;
+            self.port = Some(port);
+            self.info = info;
+            self.decoder = FrameDecoder::default();
+        }
+        if !self.supports(request)? {
+            return Ok(Frame {
+                opcode: bindings::ACK,
+                payload: Vec::new(),
+            });
+        }
+        let Some(port) = &mut self.port else {
+            unreachable!("{_FN_NAME} Reconnected above");
+        };
+
+        let opcode = request.opcode();
+        let exchanged = exchange_with(
+            port,
+            &mut self.decoder,
+            &self.options.policy,
+            &request.encode(),
+            &mut self.events,
+        )
+        .await;
+        match exchanged {
+            Ok(reply) => {
+                info!("{_FN_NAME} Received {reply:x?}");
+                Ok(reply)
+            }
+            Err(ExchangeError::Timeout { attempts }) => {
+                error!("{_FN_NAME} Arduino did not answer {opcode:#04x} in time");
+                Err(BoardError::Timeout { attempts })
+            }
+            Err(ExchangeError::Rejected { attempts }) => {
+                error!("{_FN_NAME} Arduino rejected {opcode:#04x}");
+                Err(BoardError::Rejected { attempts })
+            }
+            Err(ExchangeError::Io(e)) => Err(self.lost(e)),
+        }
+    }
+
+    /// Forgets the port after it failed with `e`, so the next request looks
+    /// for the Arduino again.
+    fn lost(&mut self, e: io::Error) -> BoardError {
+        const _FN_NAME: &str = "[serial-communicator::connection::lost]";
+
+        // => Most likely unplugged, look for it again on the next request
+        error!("{_FN_NAME} Lost the Arduino: {e}");
+        self.port = None;
+        self.subscribed = false;
+        self.events.clear();
+        BoardError::Disconnected(e)
+    }
+
+    /// Whether the board can carry out `request`, `false` if it can be
+    /// skipped instead.
+    fn supports(&self, request: &Request) -> Result<bool, BoardError> {
+        const _FN_NAME: &str = "[serial-communicator::connection::supports]";
+
+        let info = self.info;
+        let reason = match request {
+            Request::Sensor | Request::Scan if !info.sensors => "board has no sensors",
+            Request::Subscribe { .. } if !info.events => "board does not push sensor events",
+            Request::Magnet(_) if !info.magnet => "board has no magnet",
+            Request::Magnet(_) if (info.files, info.ranks) != (8, 8) => {
+                "board is not 8x8, cannot place pieces"
+            }
+            Request::Led(_) if usize::from(info.led_count) != LED_COUNT => {
+                // the hints are only a help, so play on without them
+                return Ok(false);
+            }
+            _ => return Ok(true),
+        };
+        error!("{_FN_NAME} Not sending {request}: {reason}");
+        Err(BoardError::Unsupported(reason))
+    }
+}
+
+/// The reed switch occupancy in a `SENSOR` answer.
+fn occupancy(reply: Frame) -> Result<Bitboard, BoardError> {
+    match (reply.opcode, reply.payload.as_slice()) {
+        (bindings::SENSOR, &[b0, b1, b2, b3, b4, b5, b6, b7]) => {
+            Ok(Bitboard(u64::from_le_bytes([
+                b0, b1, b2, b3, b4, b5, b6, b7,
+            ])))
+        }
+        _ => Err(BoardError::UnexpectedReply(reply)),
+    }
+}
+
+/// Opens the Arduino and waits for its firmware to be ready. Returns `None`
+/// if no device is found.
+async fn connect(
+    options: &ConnectionOptions,
+) -> Result<Option<(SerialStream, BoardInfo)>, BoardError> {
+    const _FN_NAME: &str = "[serial-communicator::connection::connect]";
+
+    let Some(mut port) = options.selector.open() else {
+        return Ok(None);
+    };
+    let info = handshake(&mut port, &options.policy, options.ready_timeout)
+        .await
+        .map_err(BoardError::Handshake)?;
+    if usize::from(info.led_count) != LED_COUNT {
+        warn!(
+            "{_FN_NAME} Board has {} LEDs, not showing hints",
+            info.led_count
+        );
+    }
+    info!("{_FN_NAME} Connected to Arduino");
+    Ok(Some((port, info)))
+}
+
+/// Looks for the Arduino until it is back, unless it comes back with firmware
+/// that cannot be used.
+async fn reconnect(options: &ConnectionOptions) -> Result<(SerialStream, BoardInfo), BoardError> {
+    const _FN_NAME: &str = "[serial-communicator::connection::reconnect]";
+
+    info!("{_FN_NAME} Waiting for the Arduino to come back");
+    loop {
+        match connect(options).await {
+            Ok(Some(connection)) => return Ok(connection),
+            Ok(None) => (),
+            Err(BoardError::Handshake(e @ (HandshakeError::NotReady | HandshakeError::Io(_)))) => {
+                warn!("{_FN_NAME} {e}");
+            }
+            Err(e) => return Err(e),
+        }
+        tokio::time::sleep(RECONNECT_INTERVAL).await;
+    }
+}
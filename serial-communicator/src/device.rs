@@ -0,0 +1,177 @@
+//! Choosing which serial device is the board.
+
+use std::fmt::{Display, Write};
+use std::str::FromStr;
+use std::time::Duration;
+
+use log::{error, info, warn};
+use tokio_serial::{SerialPortInfo, SerialPortType, SerialStream};
+
+/// The baud rate the firmware is built for.
+pub const DEFAULT_BAUD_RATE: u32 = 115_200;
+
+/// A USB vendor and product ID, written `vid:pid` in hex.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UsbId {
+    pub vid: u16,
+    pub pid: u16,
+}
+
+/// The Arduino Mega 2560 the board is built around.
+pub const ARDUINO_MEGA: UsbId = UsbId {
+    vid: 0x2341,
+    pid: 0x0042,
+};
+
+impl FromStr for UsbId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (vid, pid) = s
+            .split_once(':')
+            .ok_or_else(|| format!("expected VID:PID in hex, got {s:?}"))?;
+        let parse = |id: &str| {
+            u16::from_str_radix(id.trim_start_matches("0x"), 16)
+                .map_err(|e| format!("bad USB ID {id:?}: {e}"))
+        };
+        Ok(Self {
+            vid: parse(vid)?,
+            pid: parse(pid)?,
+        })
+    }
+}
+
+impl Display for UsbId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04x}:{:04x}", self.vid, self.pid)
+    }
+}
+
+/// Which serial device to open and how.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceSelector {
+    /// Open this device as is, without looking at what it is.
+    pub port: Option<String>,
+    /// Accept USB devices with any of these IDs.
+    pub usb_ids: Vec<UsbId>,
+    /// Only accept the USB device with this serial number.
+    pub serial_number: Option<String>,
+    pub baud_rate: u32,
+}
+
+impl Default for DeviceSelector {
+    fn default() -> Self {
+        Self {
+            port: None,
+            usb_ids: vec![ARDUINO_MEGA],
+            serial_number: None,
+            baud_rate: DEFAULT_BAUD_RATE,
+        }
+    }
+}
+
+impl DeviceSelector {
+    /// Whether the port described by `info` is one to open.
+    #[must_use]
+    pub fn matches(&self, info: &SerialPortInfo) -> bool {
+        if let Some(port) = &self.port {
+            return &info.port_name == port;
+        }
+        let SerialPortType::UsbPort(usb) = &info.port_type else {
+            return false;
+        };
+        let id = UsbId {
+            vid: usb.vid,
+            pid: usb.pid,
+        };
+        self.usb_ids.contains(&id)
+            && self
+                .serial_number
+                .as_ref()
+                .is_none_or(|wanted| usb.serial_number.as_ref() == Some(wanted))
+    }
+
+    /// The ports that match, sorted by name.
+    ///
+    /// # Errors
+    ///
+    /// Returns the `tokio_serial::Error` if the ports cannot be listed.
+    pub fn candidates(&self) -> tokio_serial::Result<Vec<SerialPortInfo>> {
+        let mut ports = tokio_serial::available_ports()?;
+        ports.retain(|info| self.matches(info));
+        ports.sort_by(|a, b| a.port_name.cmp(&b.port_name));
+        Ok(ports)
+    }
+
+    /// Opens the first matching port that can be opened, or `None` if there
+    /// is none.
+    #[must_use]
+    pub fn open(&self) -> Option<SerialStream> {
+        const _FN_NAME: &str = "[serial-communicator::device::open]";
+
+        if let Some(port_name) = &self.port {
+            // => Use the given device as is, such as a simulated board
+            return self.open_port(port_name);
+        }
+        let candidates = match self.candidates() {
+            Ok(candidates) => candidates,
+            Err(e) => {
+                error!("{_FN_NAME} Cannot list serial ports: {e}");
+                return None;
+            }
+        };
+        if candidates.len() > 1 {
+            let names = candidates.iter().map(|info| info.port_name.as_str());
+            warn!(
+                "{_FN_NAME} Several boards found ({}), choose one with --port or --serial-number",
+                names.collect::<Vec<_>>().join(", ")
+            );
+        }
+        candidates
+            .iter()
+            .find_map(|info| self.open_port(&info.port_name))
+    }
+
+    fn open_port(&self, port_name: &str) -> Option<SerialStream> {
+        const _FN_NAME: &str = "[serial-communicator::device::open_port]";
+
+        let port = tokio_serial::new(port_name, self.baud_rate).timeout(Duration::from_secs(1));
+        match SerialStream::open(&port) {
+            Ok(port) => {
+                info!("{_FN_NAME} Opened {port_name} at {} baud", self.baud_rate);
+                Some(port)
+            }
+            Err(e) => {
+                error!("{_FN_NAME} Cannot open {port_name}: {e}");
+                None
+            }
+        }
+    }
+}
+
+/// Describes `info` in one line, for listing ports.
+#[must_use]
+pub fn describe(info: &SerialPortInfo) -> String {
+    match &info.port_type {
+        SerialPortType::UsbPort(usb) => {
+            let id = UsbId {
+                vid: usb.vid,
+                pid: usb.pid,
+            };
+            let mut line = format!("{} usb {id}", info.port_name);
+            for (label, value) in [
+                ("serial", &usb.serial_number),
+                ("manufacturer", &usb.manufacturer),
+                ("product", &usb.product),
+            ] {
+                if let Some(value) = value {
+                    let _ = write!(line, " {label}={value:?}");
+                }
+            }
+            line
+        }
+        SerialPortType::PciPort => format!("{} pci", info.port_name),
+        SerialPortType::BluetoothPort => format!("{} bluetooth", info.port_name),
+        SerialPortType::Unknown => format!("{} unknown", info.port_name),
+    }
+}
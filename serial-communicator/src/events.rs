@@ -0,0 +1,71 @@
+//! Reed switch changes pushed by the firmware.
+//!
+//! After the host sends `SUBSCRIBE` with a payload of 1, the firmware sends
+//! an `EVENT` frame whenever a reed switch settles in a new state, so the
+//! host no longer has to keep asking with `SENSOR`. See
+//! [`crate::connection::BoardConnection::events`] for reading them.
+
+use std::time::Duration;
+
+use shakmaty::{Bitboard, Square};
+
+/// What happened on a square.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwitchChange {
+    Lifted,
+    Placed,
+}
+
+/// One debounced reed switch change.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwitchEvent {
+    pub square: Square,
+    pub change: SwitchChange,
+    /// When the firmware first saw the change, since it booted.
+    pub timestamp: Duration,
+}
+
+impl SwitchEvent {
+    /// Parses the payload of an `EVENT` frame: the square, 1 if a piece was
+    /// placed or 0 if it was lifted, then the milliseconds since boot (u32
+    /// LE).
+    #[must_use]
+    pub fn from_payload(payload: &[u8]) -> Option<Self> {
+        let &[square, change, t0, t1, t2, t3] = payload else {
+            return None;
+        };
+        let change = match change {
+            0 => SwitchChange::Lifted,
+            1 => SwitchChange::Placed,
+            _ => return None,
+        };
+        Some(Self {
+            square: Square::try_from(square).ok()?,
+            change,
+            timestamp: Duration::from_millis(u64::from(u32::from_le_bytes([t0, t1, t2, t3]))),
+        })
+    }
+
+    /// The payload of an `EVENT` frame describing this change. The timestamp
+    /// wraps around like the firmware's clock, after about 49 days.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn to_payload(&self) -> Vec<u8> {
+        let mut payload = vec![
+            u8::from(self.square),
+            u8::from(self.change == SwitchChange::Placed),
+        ];
+        payload.extend_from_slice(&(self.timestamp.as_millis() as u32).to_le_bytes());
+        payload
+    }
+
+    /// `occupancy` with this change made to it.
+    #[must_use]
+    pub const fn apply(&self, occupancy: Bitboard) -> Bitboard {
+        let square = Bitboard::from_square(self.square);
+        match self.change {
+            SwitchChange::Lifted => Bitboard(occupancy.0 & !square.0),
+            SwitchChange::Placed => Bitboard(occupancy.0 | square.0),
+        }
+    }
+}
@@ -0,0 +1,152 @@
+//! Framing for instructions and replies on the serial line.
+//!
+//! Every message to or from the Arduino is sent as
+//!
+//! ```text
+//! FRAME_START | opcode | length (u16 LE) | payload | CRC (u16 LE)
+//! ```
+//!
+//! where the CRC is CRC-16/CCITT-FALSE over the opcode, length and payload.
+//! The same layout is read and written by `communication.hpp` on the Arduino.
+
+use std::fmt::Display;
+
+use crate::bindings;
+
+/// Bytes before the payload: start byte, opcode and length.
+const HEADER_LEN: usize = 4;
+/// Bytes after the payload: the CRC.
+const TRAILER_LEN: usize = 2;
+
+pub const FRAME_START: u8 = bindings::FRAME_START;
+/// The longest payload the Arduino has room for.
+pub const MAX_PAYLOAD: usize = bindings::FRAME_MAX_PAYLOAD as usize;
+
+/// One decoded message.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Frame {
+    pub opcode: u8,
+    pub payload: Vec<u8>,
+}
+
+/// Why bytes that looked like a frame were dropped.
+#[derive(Debug, PartialEq, Eq)]
+pub enum FrameError {
+    /// The length field is over [`MAX_PAYLOAD`].
+    TooLong(usize),
+    /// The CRC does not match, so something in the frame was corrupted.
+    BadChecksum { expected: u16, found: u16 },
+}
+
+impl Display for FrameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::TooLong(len) => write!(f, "frame claims a {len} byte payload"),
+            Self::BadChecksum { expected, found } => {
+                write!(f, "frame checksum {found:#06x}, expected {expected:#06x}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for FrameError {}
+
+/// CRC-16/CCITT-FALSE of `bytes`, continuing from `crc` (`0xFFFF` to start).
+#[must_use]
+pub fn crc16(crc: u16, bytes: &[u8]) -> u16 {
+    bytes.iter().fold(crc, |mut crc, &byte| {
+        crc ^= u16::from(byte) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 == 0 {
+                crc << 1
+            } else {
+                (crc << 1) ^ 0x1021
+            };
+        }
+        crc
+    })
+}
+
+/// Wraps `payload` for `opcode` in a frame.
+///
+/// # Panics
+///
+/// Panics if `payload` is longer than [`MAX_PAYLOAD`].
+#[must_use]
+pub fn encode_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    assert!(
+        payload.len() <= MAX_PAYLOAD,
+        "[frame::encode_frame] {} byte payload does not fit in a frame",
+        payload.len()
+    );
+    #[allow(clippy::cast_possible_truncation)]
+    let len = (payload.len() as u16).to_le_bytes();
+
+    let mut frame = Vec::with_capacity(HEADER_LEN + payload.len() + TRAILER_LEN);
+    frame.extend_from_slice(&[FRAME_START, opcode, len[0], len[1]]);
+    frame.extend_from_slice(payload);
+    let crc = crc16(0xFFFF, &frame[1..]);
+    frame.extend_from_slice(&crc.to_le_bytes());
+    frame
+}
+
+/// Collects bytes off the serial line and splits them into frames.
+///
+/// Noise before a start byte is skipped. A frame that fails its checks is
+/// dropped from its start byte only, so a real frame starting inside it is
+/// still found.
+#[derive(Debug, Default)]
+pub struct FrameDecoder {
+    buf: Vec<u8>,
+}
+
+impl FrameDecoder {
+    pub fn push(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    /// The number of bytes received but not yet decoded.
+    #[must_use]
+    pub const fn pending(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Takes the next frame out of the received bytes.
+    ///
+    /// Returns `None` until a whole frame has arrived, and `Some(Err(_))` for
+    /// each one that had to be dropped.
+    pub fn next_frame(&mut self) -> Option<Result<Frame, FrameError>> {
+        let start = self
+            .buf
+            .iter()
+            .position(|&byte| byte == FRAME_START)
+            .unwrap_or(self.buf.len());
+        self.buf.drain(..start);
+        if self.buf.len() < HEADER_LEN {
+            return None;
+        }
+
+        let len = usize::from(u16::from_le_bytes([self.buf[2], self.buf[3]]));
+        if len > MAX_PAYLOAD {
+            self.buf.remove(0);
+            return Some(Err(FrameError::TooLong(len)));
+        }
+        let end = HEADER_LEN + len;
+        if self.buf.len() < end + TRAILER_LEN {
+            return None;
+        }
+
+        let expected = crc16(0xFFFF, &self.buf[1..end]);
+        let found = u16::from_le_bytes([self.buf[end], self.buf[end + 1]]);
+        if expected != found {
+            self.buf.remove(0);
+            return Some(Err(FrameError::BadChecksum { expected, found }));
+        }
+        let frame = Frame {
+            opcode: self.buf[1],
+            payload: self.buf[HEADER_LEN..end].to_vec(),
+        };
+        self.buf.drain(..end + TRAILER_LEN);
+        Some(Ok(frame))
+    }
+}
@@ -0,0 +1,167 @@
+//! Making sure the firmware is ready and speaks our protocol.
+//!
+//! The host sends `HANDSHAKE` with its protocol version as the only payload
+//! byte, and the firmware answers `HANDSHAKE` with a [`BoardInfo`]. As opening
+//! the port restarts the Arduino, the first few are usually lost while it
+//! boots and calibrates, so they are resent until it answers.
+
+use std::fmt::Display;
+use std::io;
+use std::time::Duration;
+
+use log::{info, warn};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::time::Instant;
+
+use crate::bindings;
+use crate::retry::{exchange, ExchangeError, RetryPolicy};
+use crate::Request;
+
+/// The version of the serial protocol spoken by this side.
+pub const PROTOCOL_VERSION: u8 = bindings::FIRMWARE_PROTOCOL_VERSION;
+
+/// What the firmware reports about itself and the board it drives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BoardInfo {
+    /// The protocol version of the firmware.
+    pub version: u8,
+    pub sensors: bool,
+    pub magnet: bool,
+    /// The number of LEDs, 0 if there are none.
+    pub led_count: u8,
+    /// Whether it can push reed switch changes as `EVENT` frames.
+    pub events: bool,
+    pub files: u8,
+    pub ranks: u8,
+}
+
+impl BoardInfo {
+    /// Parses the payload of a `HANDSHAKE` reply: the version, the
+    /// capability flags, the LED count, then the files and ranks.
+    #[must_use]
+    pub fn from_payload(payload: &[u8]) -> Option<Self> {
+        let &[version, flags, led_count, files, ranks] = payload else {
+            return None;
+        };
+        Some(Self {
+            version,
+            sensors: flags & bindings::CAP_SENSORS != 0,
+            magnet: flags & bindings::CAP_MAGNET != 0,
+            led_count: if flags & bindings::CAP_LEDS == 0 {
+                0
+            } else {
+                led_count
+            },
+            events: flags & bindings::CAP_EVENTS != 0,
+            files,
+            ranks,
+        })
+    }
+
+    /// The payload of a `HANDSHAKE` reply describing this board.
+    #[must_use]
+    pub fn to_payload(&self) -> Vec<u8> {
+        let mut flags = 0;
+        if self.sensors {
+            flags |= bindings::CAP_SENSORS;
+        }
+        if self.magnet {
+            flags |= bindings::CAP_MAGNET;
+        }
+        if self.led_count > 0 {
+            flags |= bindings::CAP_LEDS;
+        }
+        if self.events {
+            flags |= bindings::CAP_EVENTS;
+        }
+        vec![self.version, flags, self.led_count, self.files, self.ranks]
+    }
+}
+
+/// Why the firmware cannot be used.
+#[derive(Debug)]
+pub enum HandshakeError {
+    /// No answer came before the deadline.
+    NotReady,
+    /// The firmware kept answering `NAK`, so it predates the handshake.
+    Unsupported,
+    /// The firmware speaks a different protocol version.
+    VersionMismatch { firmware: u8 },
+    /// The answer could not be parsed.
+    Malformed(Vec<u8>),
+    /// The serial port failed.
+    Io(io::Error),
+}
+
+impl Display for HandshakeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotReady => write!(f, "firmware did not answer the handshake"),
+            Self::Unsupported => write!(f, "firmware does not support the handshake"),
+            Self::VersionMismatch { firmware } => write!(
+                f,
+                "firmware speaks protocol version {firmware}, expected {PROTOCOL_VERSION}"
+            ),
+            Self::Malformed(payload) => write!(f, "malformed handshake reply: {payload:x?}"),
+            Self::Io(e) => write!(f, "serial port failed: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for HandshakeError {}
+
+/// Sends `HANDSHAKE` over `port` until the firmware answers or `ready_timeout`
+/// passes, waiting `policy`'s handshake timeout for each answer.
+///
+/// # Errors
+///
+/// Returns [`HandshakeError`] if the firmware never answers, or answers with
+/// anything but our protocol version.
+pub async fn handshake<P>(
+    port: &mut P,
+    policy: &RetryPolicy,
+    ready_timeout: Duration,
+) -> Result<BoardInfo, HandshakeError>
+where
+    P: AsyncRead + AsyncWrite + Unpin,
+{
+    const _FN_NAME: &str = "[serial-communicator::handshake::handshake]";
+
+    // one attempt at a time, so the deadline is checked between them
+    let once = RetryPolicy {
+        attempts: 1,
+        ..policy.clone()
+    };
+    let hello = Request::Handshake {
+        version: PROTOCOL_VERSION,
+    }
+    .encode();
+    let deadline = Instant::now() + ready_timeout;
+    loop {
+        let rejected = match exchange(port, &once, &hello).await {
+            Ok(reply) => {
+                let info = BoardInfo::from_payload(&reply.payload)
+                    .ok_or(HandshakeError::Malformed(reply.payload))?;
+                if info.version != PROTOCOL_VERSION {
+                    return Err(HandshakeError::VersionMismatch {
+                        firmware: info.version,
+                    });
+                }
+                info!("{_FN_NAME} Firmware is ready: {info:?}");
+                return Ok(info);
+            }
+            Err(ExchangeError::Rejected { .. }) => true,
+            Err(ExchangeError::Timeout { .. }) => false,
+            Err(ExchangeError::Io(e)) => return Err(HandshakeError::Io(e)),
+        };
+        if Instant::now() >= deadline {
+            return Err(if rejected {
+                HandshakeError::Unsupported
+            } else {
+                HandshakeError::NotReady
+            });
+        }
+        warn!("{_FN_NAME} Firmware not ready yet, trying again");
+        tokio::time::sleep(policy.backoff).await;
+    }
+}
@@ -2,158 +2,471 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery)]
 
 use std::fmt::Display;
+use std::str::FromStr;
 
-use itertools::Itertools;
+use flagfall_protocol::{BoardRequest, MagnetStep, LED_COUNT};
 
-pub mod util; 
 mod bindings;
+pub mod connection;
+pub mod device;
+pub mod events;
+pub mod frame;
+pub mod handshake;
+pub mod options;
+pub mod retry;
+pub mod util;
 
-pub type Instruction = Vec<u8>; 
+/// An opcode followed by its payload, the contents of one frame.
+pub type Instruction = Vec<u8>;
 
-#[derive(PartialEq, Eq, Clone)]
+/// The bytes one magnet step takes up in an instruction.
+const MAGNET_STEP_LEN: usize = 9;
+/// The most magnet steps that fit in one frame.
+pub const MAX_MAGNET_STEPS: usize = frame::MAX_PAYLOAD / MAGNET_STEP_LEN;
+
+/// One 24-bit colour per LED, starting from h8 and ending at a1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LedFrame(Vec<u32>);
+
+impl LedFrame {
+    /// # Errors
+    ///
+    /// Returns [`RequestConversionError`] unless there are exactly
+    /// [`LED_COUNT`] colours of 24 bits each.
+    pub fn new(colours: Vec<u32>) -> Result<Self, RequestConversionError> {
+        if colours.len() != LED_COUNT {
+            return Err(RequestConversionError::WrongColourCount {
+                found: colours.len(),
+            });
+        }
+        if let Some(index) = colours.iter().position(|&colour| colour > 0xFF_FFFF) {
+            return Err(RequestConversionError::InvalidColour {
+                index,
+                colour: colours[index],
+            });
+        }
+        Ok(Self(colours))
+    }
+
+    #[must_use]
+    pub fn colours(&self) -> &[u32] {
+        &self.0
+    }
+}
+
+/// An instruction for the Arduino.
+///
+/// Besides its binary form in a frame ([`Self::encode`], [`Self::decode`]),
+/// each request has a text form such as `WRITE MAGNET 1 1 false 4 4 true`
+/// ([`Display`], [`FromStr`]), where colours are decimal `0xRRGGBB` values.
+#[derive(Debug, Clone, PartialEq)]
 pub enum Request {
-    // Read, 
-    Write(Instruction), 
+    /// Wait for the reed switches to change, then read them.
+    Sensor,
+    /// Read the reed switches once they have settled.
+    Scan,
+    /// Move the magnet carriage through the steps in order.
+    Magnet(Vec<MagnetStep>),
+    Led(LedFrame),
+    /// Ask whether the firmware is ready, telling it our protocol `version`.
+    Handshake {
+        version: u8,
+    },
+    /// Start or stop `EVENT` frames for reed switch changes.
+    Subscribe {
+        enabled: bool,
+    },
+    Ack,
+    Quit,
+}
+
+/// Why a request cannot be built. Positions count the words of the text form
+/// from 0, so `WRITE` is word 0.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RequestConversionError {
+    /// There was nothing to parse.
+    EmptyOpSequence,
+    /// The word at `position` is not `WRITE` or an operation.
+    UndefinedOpSequence {
+        token: String,
+        position: usize,
+    },
+    /// The word at `position` is not the `expected` kind of argument.
+    MalformedArgument {
+        token: String,
+        position: usize,
+        expected: &'static str,
+    },
+    /// The text ended where the `expected` argument should be.
+    MissingArgument {
+        position: usize,
+        expected: &'static str,
+    },
+    /// The operation takes no more arguments than those before `position`.
+    UnexpectedArgument {
+        token: String,
+        position: usize,
+    },
+    /// A magnet step at `index` is not a finite position.
+    InvalidStep {
+        index: usize,
+        step: MagnetStep,
+    },
+    /// More magnet steps than fit in one frame.
+    TooManySteps {
+        steps: usize,
+    },
+    WrongColourCount {
+        found: usize,
+    },
+    /// The colour at `index` has more than 24 bits.
+    InvalidColour {
+        index: usize,
+        colour: u32,
+    },
+    UndefinedOpcode(u8),
+    /// The payload does not fit the opcode.
+    MalformedPayload {
+        opcode: u8,
+        len: usize,
+    },
+}
+
+impl Display for RequestConversionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EmptyOpSequence => write!(f, "empty request"),
+            Self::UndefinedOpSequence { token, position } => {
+                write!(f, "word {position}: undefined operation {token:?}")
+            }
+            Self::MalformedArgument {
+                token,
+                position,
+                expected,
+            } => write!(f, "word {position}: expected {expected}, got {token:?}"),
+            Self::MissingArgument { position, expected } => {
+                write!(f, "word {position}: expected {expected}, got nothing")
+            }
+            Self::UnexpectedArgument { token, position } => {
+                write!(f, "word {position}: unexpected argument {token:?}")
+            }
+            Self::InvalidStep { index, step } => {
+                write!(f, "magnet step {index} is not a finite position: {step:?}")
+            }
+            Self::TooManySteps { steps } => write!(
+                f,
+                "{steps} magnet steps do not fit in one frame, at most {MAX_MAGNET_STEPS} do"
+            ),
+            Self::WrongColourCount { found } => {
+                write!(f, "expected {LED_COUNT} LED colours, got {found}")
+            }
+            Self::InvalidColour { index, colour } => {
+                write!(f, "colour {index} is not 24-bit: {colour:#x}")
+            }
+            Self::UndefinedOpcode(opcode) => write!(f, "undefined opcode {opcode:#04x}"),
+            Self::MalformedPayload { opcode, len } => {
+                write!(f, "{len}-byte payload does not fit opcode {opcode:#04x}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for RequestConversionError {}
+
+/// Checks `steps` fit in one frame and are all finite.
+fn validate_steps(steps: &[MagnetStep]) -> Result<(), RequestConversionError> {
+    if steps.len() > MAX_MAGNET_STEPS {
+        return Err(RequestConversionError::TooManySteps { steps: steps.len() });
+    }
+    if let Some(index) = steps
+        .iter()
+        .position(|step| !step.x.is_finite() || !step.y.is_finite())
+    {
+        return Err(RequestConversionError::InvalidStep {
+            index,
+            step: steps[index],
+        });
+    }
+    Ok(())
 }
 
 impl Request {
-    fn _try_parse_opcode(opword: &str) -> Result<u8, ()> {
-        match opword {
-            "SENSOR" => Ok(bindings::SENSOR), 
-            "MAGNET" => Ok(bindings::MAGNET), 
-            "LED"    => Ok(bindings::LED), 
-            // "HANDSHAKE" => bindings::HANDSHAKE, 
-            "ACK"    => Ok(bindings::ACK), 
-            "QUIT"   => Ok(bindings::QUIT), 
-            _        => Err(()), 
+    /// The opcode from `arduino_comms/opcode.h` this request is sent with.
+    #[must_use]
+    pub const fn opcode(&self) -> u8 {
+        match self {
+            Self::Sensor => bindings::SENSOR,
+            Self::Scan => bindings::SCAN,
+            Self::Magnet(_) => bindings::MAGNET,
+            Self::Led(_) => bindings::LED,
+            Self::Handshake { .. } => bindings::HANDSHAKE,
+            Self::Subscribe { .. } => bindings::SUBSCRIBE,
+            Self::Ack => bindings::ACK,
+            Self::Quit => bindings::QUIT,
         }
     }
 
-    fn _try_parse_arguments_into(
-        instr_buf: &mut Instruction, 
-        words: &mut dyn Iterator<Item = &str>
-    ) -> Result<usize, ()> {
-        if instr_buf.len() != 1 { return Err(()); }
-        let mut idx: usize = 1; 
-        let opcode = instr_buf[0]; 
-        match opcode {
-            bindings::MAGNET => {
-                let elems = words.chunks(3); 
-                for elem in &elems {
-                    if let Some((x, y, is_on)) = elem.collect_tuple() {
-                        let res = (
-                            x.parse::<f32>(), 
-                            y.parse::<f32>(), 
-                            is_on.parse::<bool>()
-                        ); 
-                        if res.0.is_err() || res.1.is_err() || res.2.is_err() { return Err(()); }
-
-                        // Insert x
-                        let x_in_le_bytes = res.0.unwrap().to_le_bytes(); 
-                        for b in x_in_le_bytes { 
-                            instr_buf.push(b); 
-                            idx += 1; 
-                        }
-
-                        // Insert y
-                        let y_in_le_bytes = res.1.unwrap().to_le_bytes(); 
-                        for b in y_in_le_bytes {
-                            instr_buf.push(b); 
-                            idx += 1; 
-                        }
-
-                        // Insert is_on: bool as u8
-                        instr_buf.push(res.2.unwrap().into()); 
-                        idx += 1; 
-                    }
-                    // Else malformed, continue.
+    const fn name(&self) -> &'static str {
+        match self {
+            Self::Sensor => "SENSOR",
+            Self::Scan => "SCAN",
+            Self::Magnet(_) => "MAGNET",
+            Self::Led(_) => "LED",
+            Self::Handshake { .. } => "HANDSHAKE",
+            Self::Subscribe { .. } => "SUBSCRIBE",
+            Self::Ack => "ACK",
+            Self::Quit => "QUIT",
+        }
+    }
+
+    /// The opcode followed by the payload: `x: f32`, `y: f32` (both
+    /// little-endian) and `is_on: u8` per magnet step, R, G and B per colour,
+    /// the protocol version for a handshake, or 1 or 0 to start or stop
+    /// events.
+    #[must_use]
+    pub fn encode(&self) -> Instruction {
+        let mut instr_buf: Instruction = vec![self.opcode()];
+        match self {
+            Self::Magnet(steps) => {
+                for step in steps {
+                    instr_buf.extend_from_slice(&step.x.to_le_bytes());
+                    instr_buf.extend_from_slice(&step.y.to_le_bytes());
+                    instr_buf.push(step.magnet.into());
                 }
-                return Ok(idx - 1); 
-            }, 
-            bindings::LED => {
-                for word in words {
-                    if let Ok(rgb_int) =  word.parse::<u32>() {
-                        let tmp = rgb_int.to_be_bytes(); 
-                        for b in &tmp[1..] {
-                            // Correctness? 
-                            instr_buf.push(*b); 
-                            idx += 1; 
-                        }
-                    }
-                    // Else malformed, continue.
+            }
+            Self::Led(frame) => {
+                for colour in frame.colours() {
+                    instr_buf.extend_from_slice(&colour.to_be_bytes()[1..]);
                 }
-                return Ok(idx - 1); 
-            }, 
-            _ => 
-                return Ok(0), 
+            }
+            Self::Handshake { version } => instr_buf.push(*version),
+            Self::Subscribe { enabled } => instr_buf.push((*enabled).into()),
+            Self::Sensor | Self::Scan | Self::Ack | Self::Quit => (),
         }
+        instr_buf
     }
-}
 
-#[derive(Debug)]
-pub enum RequestConversionError {
-    UndefinedOpSequence(String), 
-    EmptyOpSequence(String), 
-    MalformedOpSequence(String), 
+    /// Parses an instruction as made by [`Self::encode`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RequestConversionError`] if the opcode is undefined or the
+    /// payload does not fit it.
+    pub fn decode(instruction: &[u8]) -> Result<Self, RequestConversionError> {
+        let Some((&opcode, payload)) = instruction.split_first() else {
+            return Err(RequestConversionError::EmptyOpSequence);
+        };
+        let malformed = RequestConversionError::MalformedPayload {
+            opcode,
+            len: payload.len(),
+        };
+        let request = match (opcode, payload) {
+            (bindings::SENSOR, []) => Self::Sensor,
+            (bindings::SCAN, []) => Self::Scan,
+            (bindings::ACK, []) => Self::Ack,
+            (bindings::QUIT, []) => Self::Quit,
+            (bindings::HANDSHAKE, &[version]) => Self::Handshake { version },
+            (bindings::SUBSCRIBE, &[enabled @ (0 | 1)]) => Self::Subscribe {
+                enabled: enabled == 1,
+            },
+            (bindings::MAGNET, _) if payload.len() % MAGNET_STEP_LEN == 0 => {
+                let steps = payload
+                    .chunks_exact(MAGNET_STEP_LEN)
+                    .map(|chunk| match chunk[8] {
+                        0 | 1 => Ok(MagnetStep {
+                            x: f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]),
+                            y: f32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]),
+                            magnet: chunk[8] == 1,
+                        }),
+                        _ => Err(malformed.clone()),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                validate_steps(&steps)?;
+                Self::Magnet(steps)
+            }
+            (bindings::LED, _) if payload.len() % 3 == 0 => Self::Led(LedFrame::new(
+                payload
+                    .chunks_exact(3)
+                    .map(|rgb| u32::from_be_bytes([0, rgb[0], rgb[1], rgb[2]]))
+                    .collect(),
+            )?),
+            (
+                bindings::SENSOR
+                | bindings::SCAN
+                | bindings::ACK
+                | bindings::QUIT
+                | bindings::HANDSHAKE
+                | bindings::SUBSCRIBE
+                | bindings::MAGNET
+                | bindings::LED,
+                _,
+            ) => return Err(malformed),
+            _ => return Err(RequestConversionError::UndefinedOpcode(opcode)),
+        };
+        Ok(request)
+    }
 }
 
 impl Display for Request {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "WRITE {}", self.name())?;
         match self {
-            // Request::Read => 
-            //    write!(f, "READ"), 
-            Request::Write(s) => 
-                write!(f, "WRITE {:?}", s), 
+            Self::Magnet(steps) => {
+                for step in steps {
+                    write!(f, " {} {} {}", step.x, step.y, step.magnet)?;
+                }
+            }
+            Self::Led(frame) => {
+                for colour in frame.colours() {
+                    write!(f, " {colour}")?;
+                }
+            }
+            Self::Handshake { version } => write!(f, " {version}")?,
+            Self::Subscribe { enabled } => write!(f, " {enabled}")?,
+            Self::Sensor | Self::Scan | Self::Ack | Self::Quit => (),
         }
+        Ok(())
     }
 }
 
-impl TryFrom<&str> for Request {
-    type Error = RequestConversionError;
+/// The words of a request's text form, numbered for error messages.
+struct Words<'a> {
+    words: std::iter::Peekable<std::str::SplitAsciiWhitespace<'a>>,
+    /// The position of the next word.
+    position: usize,
+}
 
-    fn try_from(action: &str) -> Result<Self, Self::Error> {
-        const _FN_NAME: &str = "[Action as TryFrom::try_from]"; 
+impl<'a> Words<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            words: text.split_ascii_whitespace().peekable(),
+            position: 0,
+        }
+    }
+
+    fn next(&mut self) -> Option<(usize, &'a str)> {
+        let word = self.words.next()?;
+        self.position += 1;
+        Some((self.position - 1, word))
+    }
+
+    /// Parses the next word as `expected`.
+    fn parse<T: FromStr>(&mut self, expected: &'static str) -> Result<T, RequestConversionError> {
+        let Some((position, token)) = self.next() else {
+            return Err(RequestConversionError::MissingArgument {
+                position: self.position,
+                expected,
+            });
+        };
+        token
+            .parse()
+            .map_err(|_| RequestConversionError::MalformedArgument {
+                token: token.to_string(),
+                position,
+                expected,
+            })
+    }
+
+    fn is_empty(&mut self) -> bool {
+        self.words.peek().is_none()
+    }
+}
+
+impl FromStr for Request {
+    type Err = RequestConversionError;
+
+    fn from_str(action: &str) -> Result<Self, Self::Err> {
+        let mut words = Words::new(action);
 
         /* 1. Parse serial-communicator op */
-        let mut split = action.split_ascii_whitespace(); 
-        match split.next() {
-            // Some("READ")  => return Ok(Request::Read), 
-            Some("WRITE") => (), 
-            Some(s) => 
-                return Err(RequestConversionError::UndefinedOpSequence(
-                    format!("{_FN_NAME} Expected \"WRITE\", got {s}")
-                )), 
-            None => 
-                return Err(RequestConversionError::EmptyOpSequence(
-                    format!("{_FN_NAME} Empty sequence as input")
-                )), 
+        match words.next() {
+            Some((_, "WRITE")) => (),
+            Some((position, token)) => {
+                return Err(RequestConversionError::UndefinedOpSequence {
+                    token: token.to_string(),
+                    position,
+                })
+            }
+            None => return Err(RequestConversionError::EmptyOpSequence),
         }
 
-        /* 2. Parse Arduino op */
-        let mut instr_buf: Vec<u8> = Vec::with_capacity(512); 
-        match split.next() {
-            Some(s) => {
-                if let Ok(opcode) = Request::_try_parse_opcode(s) {
-                    instr_buf.push(opcode); 
-                } else {
-                    return Err(RequestConversionError::UndefinedOpSequence(
-                        format!("{_FN_NAME} Undefined or invalid op name in sequence: {s}")
-                    )); 
+        /* 2. Parse Arduino op and its arguments */
+        let Some((position, opword)) = words.next() else {
+            return Err(RequestConversionError::MissingArgument {
+                position: 1,
+                expected: "an operation",
+            });
+        };
+        let request = match opword {
+            "SENSOR" => Self::Sensor,
+            "SCAN" => Self::Scan,
+            "ACK" => Self::Ack,
+            "QUIT" => Self::Quit,
+            "HANDSHAKE" => Self::Handshake {
+                version: words.parse("a protocol version")?,
+            },
+            "SUBSCRIBE" => Self::Subscribe {
+                enabled: words.parse("true or false")?,
+            },
+            "MAGNET" => {
+                let mut steps = Vec::new();
+                while !words.is_empty() {
+                    steps.push(MagnetStep {
+                        x: words.parse("an x coordinate")?,
+                        y: words.parse("a y coordinate")?,
+                        magnet: words.parse("true or false")?,
+                    });
                 }
-            }, 
-            None => 
-                return Err(RequestConversionError::EmptyOpSequence(
-                    format!("{_FN_NAME} Expected Arduino operation but 0 argument provided")
-                )), 
-        }
+                validate_steps(&steps)?;
+                Self::Magnet(steps)
+            }
+            "LED" => {
+                let mut colours = Vec::new();
+                while !words.is_empty() {
+                    colours.push(words.parse("a colour")?);
+                }
+                Self::Led(LedFrame::new(colours)?)
+            }
+            _ => {
+                return Err(RequestConversionError::UndefinedOpSequence {
+                    token: opword.to_string(),
+                    position,
+                })
+            }
+        };
 
-        /* 3. Parse Arduino arguments */
-        if let Err(_) = Request::_try_parse_arguments_into(&mut instr_buf, &mut split) {
-            return Err(RequestConversionError::MalformedOpSequence(
-                format!("{_FN_NAME} Malformed argument list: {}", split.collect::<String>())
-            )); 
+        /* 3. Nothing may follow */
+        if let Some((position, token)) = words.next() {
+            return Err(RequestConversionError::UnexpectedArgument {
+                token: token.to_string(),
+                position,
+            });
         }
-             
-        return Ok(Request::Write(instr_buf)); 
+        Ok(request)
+    }
+}
+
+impl TryFrom<&str> for Request {
+    type Error = RequestConversionError;
+
+    fn try_from(action: &str) -> Result<Self, Self::Error> {
+        action.parse()
+    }
+}
+
+impl TryFrom<&BoardRequest> for Request {
+    type Error = RequestConversionError;
+
+    fn try_from(request: &BoardRequest) -> Result<Self, Self::Error> {
+        Ok(match request {
+            BoardRequest::Sense => Self::Sensor,
+            BoardRequest::Scan => Self::Scan,
+            BoardRequest::Magnet { steps } => {
+                validate_steps(steps)?;
+                Self::Magnet(steps.clone())
+            }
+            BoardRequest::Leds { colours } => Self::Led(LedFrame::new(colours.clone())?),
+            BoardRequest::Quit => Self::Quit,
+        })
     }
 }
\ No newline at end of file
@@ -1,131 +1,116 @@
 #![allow(dead_code)]
 #![warn(clippy::all, clippy::pedantic, clippy::nursery)]
 
+//! Serves the board over stdin and stdout with the `flagfall-protocol`
+//! messages, a thin wrapper around [`BoardConnection`] for other programs and
+//! for debugging by hand.
+
 use std::io;
-use std::io::Write; 
-use std::time::Duration;
 
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio_serial::SerialStream;
+use flagfall_protocol::{read_message, write_message, BoardReply, BoardRequest, ProtocolError};
 use log::{error, info};
 
-use serial_communicator::{Request, Instruction}; 
+use serial_communicator::connection::{BoardConnection, BoardError};
+use serial_communicator::device::{describe, DeviceSelector};
 
-mod util;
 mod bindings;
+mod cliargs;
+mod util;
 
-const BAUD_RATE: u32 = 115_200; 
-
-fn _find_devices() -> Vec<SerialStream> {
-    const _FN_NAME: &str = "[serial-communicator::_find_devices]";
+/// Prints every serial port, marking those `selector` would open.
+fn list_ports(selector: &DeviceSelector) {
+    const _FN_NAME: &str = "[serial-communicator::list_ports]";
 
-    let mut port_buf: Vec<SerialStream> = Vec::new(); 
-    if let Ok(ports) = tokio_serial::available_ports() {
-        for port_info in ports {
-            let port_type = port_info.port_type;
-            if let tokio_serial::SerialPortType::UsbPort(ref info) = port_type {
-                if info.vid != 0x2341 || info.pid != 0x0042 {
-                    continue; 
-                }
-            } else {
-                continue; 
-            }
-            let port = tokio_serial::new(port_info.port_name, BAUD_RATE)
-                .timeout(Duration::from_secs(1)); 
-            if let Ok(port) = SerialStream::open(&port) {
-                port_buf.push(port); 
+    match tokio_serial::available_ports() {
+        Ok(ports) => {
+            for info in ports {
+                let mark = if selector.matches(&info) { '*' } else { ' ' };
+                println!("{mark} {}", describe(&info));
             }
         }
+        Err(e) => error!("{_FN_NAME} Cannot list serial ports: {e}"),
     }
-    return port_buf; 
 }
 
-async fn write_and_wait_response(
-    port_stream: &mut SerialStream, 
-    instruction: Instruction
-) -> io::Result<Instruction> {
-    const _FN_NAME: &str = "[serial-communicator::write_and_wait_response]"; 
-    let opcode = instruction[0]; 
+/// Sends `reply` to the master program, returning `false` if stdout is gone.
+fn send_reply(reply: &BoardReply) -> bool {
+    const _FN_NAME: &str = "[serial-communicator::send_reply]";
 
-    port_stream.writable().await?; 
-    AsyncWriteExt::write_all(port_stream, &instruction).await?; 
-    AsyncWriteExt::flush(port_stream).await?; 
-
-    port_stream.readable().await?; 
-    let mut response_buf: Vec<u8> = Vec::with_capacity(8);  
-    if opcode == bindings::SENSOR { 
-        // => Wait for 8 bytes
-        response_buf = vec![0; 8]; 
-        AsyncReadExt::read_exact(port_stream, &mut response_buf).await?; 
-    } else {
-        // => Wait for 1 byte
-        AsyncReadExt::read_buf(port_stream, &mut response_buf).await?; 
+    if let Err(e) = write_message(&mut io::stdout().lock(), reply) {
+        error!("{_FN_NAME} Unexpected error when writing to stdout: \n{e:#?}");
+        return false;
     }
-    
-    info!("{_FN_NAME} Received {:x?}", response_buf); 
-    return Ok(response_buf); 
+    true
 }
 
 #[tokio::main]
 async fn main() {
     const _FN_NAME: &str = "[serial-communicator::main]";
-    simple_logger::init_with_env().unwrap(); 
+    simple_logger::init_with_env().unwrap();
+    let args = <cliargs::Cli as clap::Parser>::parse();
+    if args.list_ports {
+        list_ports(&args.connection.device_selector());
+        return;
+    }
 
     /* 1. Find Arduino device -- ONE device */
-    let mut port_streams = _find_devices(); 
-    if port_streams.is_empty() {
-        error!("{_FN_NAME} Cannot find serial devices. Quitting..."); 
-        return; 
-    }
-    let mut port_stream = port_streams.pop().unwrap(); 
-    std::thread::sleep(std::time::Duration::from_secs(3)); 
-    info!("{_FN_NAME} Connected to Arduino"); 
+    let mut connection = match BoardConnection::open(args.connection.connection_options()).await {
+        Ok(connection) => connection,
+        Err(BoardError::NotFound) => {
+            error!("{_FN_NAME} Cannot find serial devices. Quitting...");
+            return;
+        }
+        Err(e) => {
+            error!("{_FN_NAME} Cannot use Arduino: {e}. Quitting...");
+            return;
+        }
+    };
 
-    let mut action_buffer: String = String::with_capacity(1024);
-    // let mut read_buffer:   Vec<u8> = vec![0; 1024]; 
-    
     loop {
-        /* 2. Read from `stdin` and re-send to Arduino */
-        action_buffer.clear();
-        let action; 
-        match io::stdin().read_line(&mut action_buffer) {
-            Ok(0) => {
+        /* 2. Read a request from `stdin` and re-send to Arduino */
+        let read = read_message::<_, BoardRequest>(&mut io::stdin().lock());
+        let request = match read {
+            Ok(Some(request)) => request,
+            Ok(None) => {
                 // => EOF reached, close pipe
                 info!("{_FN_NAME} EOF reached at stdin");
-                return; 
-            },
-            Ok(_) => {
-                // => Try convert to `Action` instance
-                action = Request::try_from(action_buffer.as_ref())
-            },
-            Err(e) => {
-                error!("{_FN_NAME} Unexpected error when reading from stdin: \n{:#?}", e);
                 return;
             }
+            Err(ProtocolError::Io(e)) => {
+                error!("{_FN_NAME} Unexpected error when reading from stdin: \n{e:#?}");
+                return;
+            }
+            Err(e) => {
+                // => Answer anyway so the master program does not wait forever
+                error!("{_FN_NAME} Invalid input from stdin: {e}");
+                if !send_reply(&BoardReply::Error {
+                    message: e.to_string(),
+                }) {
+                    return;
+                }
+                continue;
+            }
         };
+        if request == BoardRequest::Quit {
+            info!("{_FN_NAME} Quit requested");
+            send_reply(&BoardReply::Done);
+            return;
+        }
 
-        match action {
-            Ok(Request::Write(v)) => {
-                // => Write to Arduino, then wait on response and send to stdout
-                match write_and_wait_response(&mut port_stream, v).await {
-                    Ok(response) => {
-                        if let Err(e) = io::stdout().write_all(&response) {
-                            error!("{_FN_NAME} WRITE: Unexpected error when writing to stdout: \n{:#?}", e); 
-                            return; 
-                        }
-                        if let Err(e) = io::stdout().flush() {
-                            error!("{_FN_NAME} WRITE: Unexpected error when flushing stdout: \n{:#?}", e); 
-                            return; 
-                        }
-                    }, 
-                    Err(e) => {
-                        error!("{_FN_NAME} WRITE: Unexpected error when requesting Arduino: \n{:#?}", e); 
-                        return; 
-                    }
-                }  
-            }, 
-            Err(e) => 
-                error!("{_FN_NAME} Invalid input from stdin: \n{:#?}", e), 
+        /* 3. Write to Arduino, then wait on response and send to stdout */
+        let reply = match connection.request(&request).await {
+            Ok(reply) => reply,
+            Err(e) => {
+                // => Someone flashed firmware we cannot talk to, refuse to go on
+                error!("{_FN_NAME} Cannot use Arduino: {e}. Quitting...");
+                send_reply(&BoardReply::Error {
+                    message: e.to_string(),
+                });
+                return;
+            }
+        };
+        if !send_reply(&reply) {
+            return;
         }
     }
 }
@@ -0,0 +1,102 @@
+//! Command line options for connecting to the board, shared by every binary
+//! that does.
+
+use std::time::Duration;
+
+use clap::Args;
+
+use crate::connection::ConnectionOptions;
+use crate::device::{DeviceSelector, UsbId, DEFAULT_BAUD_RATE};
+use crate::retry::RetryPolicy;
+
+/// The options behind [`ConnectionOptions`], to flatten into a binary's own.
+#[derive(Args, Debug, Clone)]
+pub struct ConnectionArgs {
+    /// Serial device to use instead of searching for the board, such as the
+    /// pseudo-terminal of `board-simulator`.
+    #[clap(long, value_name = "PATH", env = "FLAGFALL_SERIAL_PORT")]
+    pub port: Option<String>,
+    /// USB IDs of the boards to search for, for Arduino clones and other
+    /// boards. Repeat or separate with commas.
+    #[clap(
+        long = "usb-id",
+        value_name = "VID:PID",
+        env = "FLAGFALL_USB_IDS",
+        value_delimiter = ',',
+        default_value = "2341:0042"
+    )]
+    pub usb_ids: Vec<UsbId>,
+    /// Only use the USB board with this serial number.
+    #[clap(long, value_name = "SERIAL", env = "FLAGFALL_SERIAL_NUMBER")]
+    pub serial_number: Option<String>,
+    /// Baud rate the board's firmware talks at.
+    #[clap(long, value_name = "BAUD", env = "FLAGFALL_BAUD_RATE", default_value_t = DEFAULT_BAUD_RATE)]
+    pub baud: u32,
+    /// Seconds to wait after opening the port for the firmware to answer the
+    /// handshake, which includes restarting and calibrating.
+    #[clap(long, value_name = "SECS", value_parser = parse_seconds, default_value = "30")]
+    pub ready_timeout: Duration,
+    /// Seconds to wait for each handshake answer.
+    #[clap(long, value_name = "SECS", value_parser = parse_seconds, default_value = "1")]
+    pub handshake_timeout: Duration,
+    /// Give up on a SENSOR read after this many seconds instead of waiting
+    /// for the player indefinitely.
+    #[clap(long, value_name = "SECS", value_parser = parse_seconds)]
+    pub sensor_timeout: Option<Duration>,
+    /// Seconds to wait for a SCAN reading.
+    #[clap(long, value_name = "SECS", value_parser = parse_seconds, default_value = "2")]
+    pub scan_timeout: Duration,
+    /// Seconds a whole MAGNET move may take. A late MAGNET is never resent.
+    #[clap(long, value_name = "SECS", value_parser = parse_seconds, default_value = "60")]
+    pub magnet_timeout: Duration,
+    /// Seconds to wait for the LEDs to be set.
+    #[clap(long, value_name = "SECS", value_parser = parse_seconds, default_value = "2")]
+    pub led_timeout: Duration,
+    /// How many times to send an instruction before giving up.
+    #[clap(long, value_name = "N", default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..))]
+    pub attempts: u32,
+    /// Milliseconds to wait before the first resend, doubled for each one
+    /// after.
+    #[clap(long, value_name = "MS", default_value_t = 200)]
+    pub backoff_ms: u64,
+}
+
+impl ConnectionArgs {
+    #[must_use]
+    pub fn connection_options(&self) -> ConnectionOptions {
+        ConnectionOptions {
+            selector: self.device_selector(),
+            policy: self.retry_policy(),
+            ready_timeout: self.ready_timeout,
+        }
+    }
+
+    #[must_use]
+    pub fn device_selector(&self) -> DeviceSelector {
+        DeviceSelector {
+            port: self.port.clone(),
+            usb_ids: self.usb_ids.clone(),
+            serial_number: self.serial_number.clone(),
+            baud_rate: self.baud,
+        }
+    }
+
+    #[must_use]
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            sensor_timeout: self.sensor_timeout,
+            scan_timeout: self.scan_timeout,
+            magnet_timeout: self.magnet_timeout,
+            led_timeout: self.led_timeout,
+            handshake_timeout: self.handshake_timeout,
+            attempts: self.attempts,
+            backoff: Duration::from_millis(self.backoff_ms),
+            ..RetryPolicy::default()
+        }
+    }
+}
+
+fn parse_seconds(secs: &str) -> Result<Duration, String> {
+    let secs = secs.parse::<f64>().map_err(|e| e.to_string())?;
+    Duration::try_from_secs_f64(secs).map_err(|e| e.to_string())
+}
@@ -0,0 +1,281 @@
+//! Sending instructions to the Arduino with timeouts and retries.
+//!
+//! An instruction is sent again when the Arduino answers `NAK`, meaning it
+//! got a corrupt frame and did nothing, or when no reply arrives in time.
+//! `MAGNET` is the exception to the latter: the carriage may already have
+//! moved, so a late `MAGNET` is reported as [`ExchangeError::Timeout`]
+//! rather than repeated.
+//!
+//! `EVENT` frames the firmware pushes in the meantime are not answers to
+//! anything, so [`exchange_with`] sets them aside for the caller. Any other
+//! frame already waiting when an instruction is sent answers an instruction
+//! given up on before, and is discarded.
+
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::io;
+use std::time::Duration;
+
+use futures_util::FutureExt;
+use log::warn;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::bindings;
+use crate::frame::{encode_frame, Frame, FrameDecoder};
+
+/// How long to wait for replies and how often to try.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How long a `SENSOR` read may wait for the player, `None` for as long
+    /// as it takes.
+    pub sensor_timeout: Option<Duration>,
+    pub scan_timeout: Duration,
+    /// How long a whole `MAGNET` move may take.
+    pub magnet_timeout: Duration,
+    pub led_timeout: Duration,
+    /// How long to wait for each `HANDSHAKE` answer while the Arduino boots.
+    pub handshake_timeout: Duration,
+    /// How long any other instruction may take.
+    pub default_timeout: Duration,
+    /// How many times an instruction is sent before giving up.
+    pub attempts: u32,
+    /// The pause before the first resend, doubled for each one after.
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    // `Duration::from_mins` needs a newer toolchain than the rest of the crate
+    #[allow(clippy::duration_suboptimal_units)]
+    fn default() -> Self {
+        Self {
+            sensor_timeout: None,
+            scan_timeout: Duration::from_secs(2),
+            magnet_timeout: Duration::from_secs(60),
+            led_timeout: Duration::from_secs(2),
+            handshake_timeout: Duration::from_secs(1),
+            default_timeout: Duration::from_secs(2),
+            attempts: 3,
+            backoff: Duration::from_millis(200),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// How long to wait for the reply to `opcode`, `None` for no limit.
+    #[must_use]
+    pub const fn timeout(&self, opcode: u8) -> Option<Duration> {
+        match opcode {
+            bindings::SENSOR => self.sensor_timeout,
+            bindings::SCAN => Some(self.scan_timeout),
+            bindings::MAGNET => Some(self.magnet_timeout),
+            bindings::LED => Some(self.led_timeout),
+            bindings::HANDSHAKE => Some(self.handshake_timeout),
+            _ => Some(self.default_timeout),
+        }
+    }
+}
+
+/// Why an instruction got no usable reply.
+#[derive(Debug)]
+pub enum ExchangeError {
+    /// Nothing came back in time, the last time after `attempts` tries.
+    Timeout { attempts: u32 },
+    /// The Arduino answered `NAK` to all `attempts` tries.
+    Rejected { attempts: u32 },
+    /// The serial port failed.
+    Io(io::Error),
+}
+
+impl Display for ExchangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Timeout { attempts } => write!(f, "no reply after {attempts} attempts"),
+            Self::Rejected { attempts } => write!(f, "rejected {attempts} times"),
+            Self::Io(e) => write!(f, "serial port failed: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ExchangeError {}
+
+impl From<io::Error> for ExchangeError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// The opcode of the frame that answers `opcode`.
+#[must_use]
+pub const fn reply_opcode(opcode: u8) -> u8 {
+    match opcode {
+        bindings::SENSOR | bindings::SCAN => bindings::SENSOR,
+        bindings::HANDSHAKE => bindings::HANDSHAKE,
+        _ => bindings::ACK,
+    }
+}
+
+enum Reply {
+    Answer(Frame),
+    Nak,
+}
+
+/// Sends `instruction` (an opcode followed by its payload) over `port` and
+/// waits for the answer, resending it as `policy` allows.
+///
+/// # Errors
+///
+/// Returns [`ExchangeError`] once `policy` gives up, or at once if `port`
+/// fails.
+pub async fn exchange<P>(
+    port: &mut P,
+    policy: &RetryPolicy,
+    instruction: &[u8],
+) -> Result<Frame, ExchangeError>
+where
+    P: AsyncRead + AsyncWrite + Unpin,
+{
+    const _FN_NAME: &str = "[serial-communicator::retry::exchange]";
+
+    let mut events = VecDeque::new();
+    let result = exchange_with(
+        port,
+        &mut FrameDecoder::default(),
+        policy,
+        instruction,
+        &mut events,
+    )
+    .await;
+    if !events.is_empty() {
+        warn!("{_FN_NAME} Dropping {} sensor events", events.len());
+    }
+    result
+}
+
+/// Like [`exchange`], but reads through `decoder`, so whatever arrives after
+/// the answer is kept for the next read, and puts the `EVENT` frames that
+/// arrive before it on the end of `events`.
+///
+/// Other frames left in `decoder` or on `port` from earlier instructions are
+/// discarded before sending.
+///
+/// # Errors
+///
+/// Returns [`ExchangeError`] once `policy` gives up, or at once if `port`
+/// fails.
+pub async fn exchange_with<P>(
+    port: &mut P,
+    decoder: &mut FrameDecoder,
+    policy: &RetryPolicy,
+    instruction: &[u8],
+    events: &mut VecDeque<Frame>,
+) -> Result<Frame, ExchangeError>
+where
+    P: AsyncRead + AsyncWrite + Unpin,
+{
+    const _FN_NAME: &str = "[serial-communicator::retry::exchange_with]";
+
+    let opcode = instruction[0];
+    let frame = encode_frame(opcode, &instruction[1..]);
+    // => A late answer to an instruction that timed out must not be taken
+    //    for the answer to this one
+    discard_stale(port, decoder, events)?;
+    // `decoder` is kept across attempts, so a late answer to one still
+    // counts for the next
+    let mut backoff = policy.backoff;
+    let mut last_error = ExchangeError::Timeout { attempts: 0 };
+
+    for attempt in 1..=policy.attempts {
+        port.write_all(&frame).await?;
+        port.flush().await?;
+
+        let reply = read_reply(port, decoder, reply_opcode(opcode), events);
+        let reply = match policy.timeout(opcode) {
+            Some(limit) => tokio::time::timeout(limit, reply).await.ok(),
+            None => Some(reply.await),
+        };
+        match reply {
+            Some(Ok(Reply::Answer(answer))) => return Ok(answer),
+            Some(Ok(Reply::Nak)) => {
+                warn!("{_FN_NAME} Arduino rejected {opcode:#04x}, attempt {attempt}");
+                last_error = ExchangeError::Rejected { attempts: attempt };
+            }
+            Some(Err(e)) => return Err(e.into()),
+            None if opcode == bindings::MAGNET => {
+                warn!("{_FN_NAME} No reply to MAGNET, not moving the carriage again");
+                return Err(ExchangeError::Timeout { attempts: attempt });
+            }
+            None => {
+                warn!("{_FN_NAME} No reply to {opcode:#04x}, attempt {attempt}");
+                last_error = ExchangeError::Timeout { attempts: attempt };
+            }
+        }
+        if attempt < policy.attempts {
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+    Err(last_error)
+}
+
+/// Decodes whatever `port` already holds, without waiting for more, and
+/// drops every whole frame but the `EVENT` frames, which go on `events`.
+fn discard_stale<P>(
+    port: &mut P,
+    decoder: &mut FrameDecoder,
+    events: &mut VecDeque<Frame>,
+) -> io::Result<()>
+where
+    P: AsyncRead + Unpin,
+{
+    const _FN_NAME: &str = "[serial-communicator::retry::discard_stale]";
+
+    let mut read_buf = [0_u8; 64];
+    while let Some(read) = port.read(&mut read_buf).now_or_never() {
+        let read = read?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        decoder.push(&read_buf[..read]);
+    }
+    while let Some(decoded) = decoder.next_frame() {
+        match decoded {
+            Ok(frame) if frame.opcode == bindings::EVENT => events.push_back(frame),
+            Ok(frame) => warn!("{_FN_NAME} Discarding stale {frame:x?}"),
+            Err(e) => warn!("{_FN_NAME} Dropping a corrupt frame: {e}"),
+        }
+    }
+    Ok(())
+}
+
+/// Reads until a frame with `expected` opcode or a `NAK` arrives, setting
+/// `EVENT` frames aside on `events` and skipping corrupt frames and stale
+/// answers to earlier instructions.
+async fn read_reply<P>(
+    port: &mut P,
+    decoder: &mut FrameDecoder,
+    expected: u8,
+    events: &mut VecDeque<Frame>,
+) -> io::Result<Reply>
+where
+    P: AsyncRead + Unpin,
+{
+    const _FN_NAME: &str = "[serial-communicator::retry::read_reply]";
+
+    let mut read_buf = [0_u8; 64];
+    loop {
+        while let Some(decoded) = decoder.next_frame() {
+            match decoded {
+                Ok(frame) if frame.opcode == expected => return Ok(Reply::Answer(frame)),
+                Ok(frame) if frame.opcode == bindings::NAK => return Ok(Reply::Nak),
+                Ok(frame) if frame.opcode == bindings::EVENT => events.push_back(frame),
+                Ok(frame) => warn!("{_FN_NAME} Skipping unexpected {frame:x?}"),
+                Err(e) => warn!("{_FN_NAME} Dropping a corrupt frame: {e}"),
+            }
+        }
+        let read = port.read(&mut read_buf).await?;
+        if read == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        decoder.push(&read_buf[..read]);
+    }
+}
@@ -0,0 +1,66 @@
+extern crate serial_communicator;
+
+use serial_communicator::device::{DeviceSelector, UsbId, ARDUINO_MEGA};
+use tokio_serial::{SerialPortInfo, SerialPortType, UsbPortInfo};
+
+fn _usb_port(name: &str, vid: u16, pid: u16, serial_number: Option<&str>) -> SerialPortInfo {
+    SerialPortInfo {
+        port_name: name.to_string(),
+        port_type: SerialPortType::UsbPort(UsbPortInfo {
+            vid,
+            pid,
+            serial_number: serial_number.map(str::to_string),
+            manufacturer: None,
+            product: None,
+        }),
+    }
+}
+
+#[test]
+fn test_usb_id_parsing() {
+    assert_eq!("2341:0042".parse::<UsbId>(), Ok(ARDUINO_MEGA));
+    assert_eq!("0x2341:0x42".parse::<UsbId>(), Ok(ARDUINO_MEGA));
+    assert_eq!(ARDUINO_MEGA.to_string(), "2341:0042");
+    assert!("2341".parse::<UsbId>().is_err());
+    assert!("2341:zz".parse::<UsbId>().is_err());
+    assert!("12345:0042".parse::<UsbId>().is_err());
+}
+
+#[test]
+fn test_selector_matching() {
+    let mega = _usb_port("/dev/ttyACM0", 0x2341, 0x0042, Some("A1"));
+    let clone = _usb_port("/dev/ttyUSB0", 0x1a86, 0x7523, None);
+    let builtin = SerialPortInfo {
+        port_name: "/dev/ttyS0".to_string(),
+        port_type: SerialPortType::Unknown,
+    };
+
+    let selector = DeviceSelector::default();
+    assert!(selector.matches(&mega));
+    assert!(!selector.matches(&clone));
+    assert!(!selector.matches(&builtin));
+
+    let selector = DeviceSelector {
+        usb_ids: vec![ARDUINO_MEGA, "1a86:7523".parse().unwrap()],
+        ..DeviceSelector::default()
+    };
+    assert!(selector.matches(&mega));
+    assert!(selector.matches(&clone));
+
+    // a serial number rules out boards without one
+    let selector = DeviceSelector {
+        serial_number: Some("A1".to_string()),
+        ..selector
+    };
+    assert!(selector.matches(&mega));
+    assert!(!selector.matches(&clone));
+    assert!(!selector.matches(&_usb_port("/dev/ttyACM1", 0x2341, 0x0042, Some("B2"))));
+
+    // a port path is taken as is
+    let selector = DeviceSelector {
+        port: Some("/dev/ttyS0".to_string()),
+        ..DeviceSelector::default()
+    };
+    assert!(selector.matches(&builtin));
+    assert!(!selector.matches(&mega));
+}
@@ -0,0 +1,48 @@
+extern crate serial_communicator;
+
+use std::time::Duration;
+
+use serial_communicator::events::{SwitchChange, SwitchEvent};
+use shakmaty::{Bitboard, Square};
+
+#[test]
+fn test_event_payload() {
+    let event = SwitchEvent {
+        square: Square::E2,
+        change: SwitchChange::Lifted,
+        timestamp: Duration::from_millis(70_000),
+    };
+    assert_eq!(event.to_payload(), [12, 0, 0x70, 0x11, 0x01, 0x00]);
+    assert_eq!(SwitchEvent::from_payload(&event.to_payload()), Some(event));
+
+    let placed = SwitchEvent::from_payload(&[63, 1, 0, 0, 0, 0]).unwrap();
+    assert_eq!(placed.square, Square::H8);
+    assert_eq!(placed.change, SwitchChange::Placed);
+
+    // not a square, not a change, and cut short
+    assert_eq!(SwitchEvent::from_payload(&[64, 1, 0, 0, 0, 0]), None);
+    assert_eq!(SwitchEvent::from_payload(&[0, 2, 0, 0, 0, 0]), None);
+    assert_eq!(SwitchEvent::from_payload(&[0, 1, 0, 0]), None);
+}
+
+#[test]
+fn test_event_apply() {
+    let start = Bitboard(0xFFFF_0000_0000_FFFF);
+    let lift = SwitchEvent {
+        square: Square::E2,
+        change: SwitchChange::Lifted,
+        timestamp: Duration::ZERO,
+    };
+    let place = SwitchEvent {
+        square: Square::E4,
+        change: SwitchChange::Placed,
+        ..lift
+    };
+    let moved = place.apply(lift.apply(start));
+    assert_eq!(
+        moved,
+        start ^ Bitboard::from_square(Square::E2) ^ Bitboard::from_square(Square::E4)
+    );
+    // a repeated event changes nothing
+    assert_eq!(place.apply(moved), moved);
+}
@@ -0,0 +1,91 @@
+extern crate serial_communicator;
+
+use serial_communicator::frame::{
+    crc16, encode_frame, Frame, FrameDecoder, FrameError, FRAME_START, MAX_PAYLOAD,
+};
+
+fn _decode_all(bytes: &[u8]) -> Vec<Result<Frame, FrameError>> {
+    let mut decoder = FrameDecoder::default();
+    decoder.push(bytes);
+    std::iter::from_fn(|| decoder.next_frame()).collect()
+}
+
+#[test]
+fn test_crc_check_value() {
+    // the standard check value of CRC-16/CCITT-FALSE
+    assert_eq!(crc16(0xFFFF, b"123456789"), 0x29B1);
+}
+
+#[test]
+fn test_frame_layout() {
+    let frame = encode_frame(0x01, &[]);
+    assert_eq!(&frame[..4], &[FRAME_START, 0x01, 0, 0]);
+    assert_eq!(frame.len(), 6);
+
+    let frame = encode_frame(0x03, &[0xAA; 300]);
+    assert_eq!(&frame[..4], &[FRAME_START, 0x03, 0x2C, 0x01]);
+    assert_eq!(frame.len(), 4 + 300 + 2);
+}
+
+#[test]
+fn test_split_and_concatenated_frames() {
+    let mut bytes = encode_frame(0x01, &0x1234_u64.to_le_bytes());
+    bytes.extend(encode_frame(0x20, &[]));
+
+    // one byte at a time, like a slow serial line
+    let mut decoder = FrameDecoder::default();
+    let mut frames = Vec::new();
+    for byte in &bytes {
+        decoder.push(&[*byte]);
+        while let Some(frame) = decoder.next_frame() {
+            frames.push(frame.unwrap());
+        }
+    }
+    assert_eq!(
+        frames,
+        vec![
+            Frame {
+                opcode: 0x01,
+                payload: 0x1234_u64.to_le_bytes().to_vec()
+            },
+            Frame {
+                opcode: 0x20,
+                payload: Vec::new()
+            },
+        ]
+    );
+    assert_eq!(decoder.pending(), 0);
+}
+
+#[test]
+fn test_resync_after_noise_and_corruption() {
+    let ack = encode_frame(0x20, &[]);
+    let mut corrupted = encode_frame(0x01, &[1, 2, 3, 4, 5, 6, 7, 8]);
+    corrupted[6] ^= 0xFF;
+
+    let mut bytes = vec![0x00, 0x42, FRAME_START];
+    bytes.extend(&corrupted);
+    bytes.extend(&ack);
+    let decoded = _decode_all(&bytes);
+
+    // the stray start byte and the corrupted frame are reported, then the
+    // ACK is found again
+    assert!(decoded
+        .iter()
+        .any(|frame| matches!(frame, Err(FrameError::BadChecksum { .. }))));
+    assert_eq!(
+        decoded.last(),
+        Some(&Ok(Frame {
+            opcode: 0x20,
+            payload: Vec::new()
+        }))
+    );
+}
+
+#[test]
+fn test_oversized_length_is_rejected() {
+    #[allow(clippy::cast_possible_truncation)]
+    let len = ((MAX_PAYLOAD + 1) as u16).to_le_bytes();
+    let decoded = _decode_all(&[FRAME_START, 0x02, len[0], len[1]]);
+    assert_eq!(decoded, vec![Err(FrameError::TooLong(MAX_PAYLOAD + 1))]);
+}
@@ -0,0 +1,130 @@
+extern crate serial_communicator;
+
+use std::time::Duration;
+
+use serial_communicator::frame::{encode_frame, FrameDecoder};
+use serial_communicator::handshake::{handshake, BoardInfo, HandshakeError, PROTOCOL_VERSION};
+use serial_communicator::retry::RetryPolicy;
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+const HANDSHAKE: u8 = 0x10;
+const NAK: u8 = 0x21;
+
+const INFO: BoardInfo = BoardInfo {
+    version: PROTOCOL_VERSION,
+    sensors: true,
+    magnet: true,
+    led_count: 64,
+    events: false,
+    files: 8,
+    ranks: 8,
+};
+
+fn _policy() -> RetryPolicy {
+    RetryPolicy {
+        handshake_timeout: Duration::from_millis(50),
+        backoff: Duration::from_millis(10),
+        ..RetryPolicy::default()
+    }
+}
+
+fn _reply(info: &BoardInfo) -> Vec<u8> {
+    encode_frame(HANDSHAKE, &info.to_payload())
+}
+
+/// Answers each `HANDSHAKE` it receives with the next of `replies`, where an
+/// empty reply means staying silent, as while booting. Returns how many it
+/// received.
+async fn _fake_arduino(mut port: DuplexStream, replies: Vec<Vec<u8>>) -> usize {
+    let mut decoder = FrameDecoder::default();
+    let mut replies = replies.into_iter();
+    let mut received = 0;
+    let mut buf = [0_u8; 64];
+    loop {
+        let read = match port.read(&mut buf).await {
+            Ok(0) | Err(_) => return received,
+            Ok(read) => read,
+        };
+        decoder.push(&buf[..read]);
+        while let Some(Ok(frame)) = decoder.next_frame() {
+            assert_eq!(frame.opcode, HANDSHAKE);
+            assert_eq!(frame.payload, [PROTOCOL_VERSION]);
+            received += 1;
+            if let Some(reply) = replies.next() {
+                port.write_all(&reply).await.unwrap();
+            }
+        }
+    }
+}
+
+async fn _handshake(
+    replies: Vec<Vec<u8>>,
+    ready_timeout: Duration,
+) -> (Result<BoardInfo, HandshakeError>, usize) {
+    let (mut host, arduino) = tokio::io::duplex(1024);
+    let arduino = tokio::spawn(_fake_arduino(arduino, replies));
+    let result = handshake(&mut host, &_policy(), ready_timeout).await;
+    drop(host);
+    (result, arduino.await.unwrap())
+}
+
+#[test]
+fn test_board_info_payload() {
+    assert_eq!(BoardInfo::from_payload(&INFO.to_payload()), Some(INFO));
+    assert_eq!(BoardInfo::from_payload(&[PROTOCOL_VERSION, 0x01, 64]), None);
+
+    // the LED count means nothing without the LED flag
+    let info = BoardInfo::from_payload(&[PROTOCOL_VERSION, 0x03, 64, 8, 8]).unwrap();
+    assert!(info.sensors && info.magnet && !info.events);
+    assert_eq!(info.led_count, 0);
+
+    let info = BoardInfo::from_payload(&[PROTOCOL_VERSION, 0x09, 0, 8, 8]).unwrap();
+    assert!(info.sensors && info.events && !info.magnet);
+}
+
+#[tokio::test]
+async fn test_handshake_waits_for_firmware() {
+    let (result, received) = _handshake(
+        vec![Vec::new(), Vec::new(), _reply(&INFO)],
+        Duration::from_secs(5),
+    )
+    .await;
+    assert_eq!(result.unwrap(), INFO);
+    assert_eq!(received, 3);
+}
+
+#[tokio::test]
+async fn test_handshake_refuses_other_versions() {
+    let newer = BoardInfo {
+        version: PROTOCOL_VERSION + 1,
+        ..INFO
+    };
+    let (result, received) = _handshake(vec![_reply(&newer)], Duration::from_secs(5)).await;
+    assert!(matches!(
+        result,
+        Err(HandshakeError::VersionMismatch { firmware }) if firmware == PROTOCOL_VERSION + 1
+    ));
+    assert_eq!(received, 1);
+
+    let (result, _) = _handshake(
+        vec![encode_frame(HANDSHAKE, &[PROTOCOL_VERSION])],
+        Duration::from_secs(5),
+    )
+    .await;
+    assert!(matches!(result, Err(HandshakeError::Malformed(_))));
+}
+
+#[tokio::test]
+async fn test_handshake_gives_up() {
+    let (result, received) = _handshake(Vec::new(), Duration::from_millis(200)).await;
+    assert!(matches!(result, Err(HandshakeError::NotReady)));
+    assert!(received > 1);
+
+    // firmware from before the handshake rejects it as an unknown instruction
+    let (result, _) = _handshake(
+        vec![encode_frame(NAK, &[]); 100],
+        Duration::from_millis(200),
+    )
+    .await;
+    assert!(matches!(result, Err(HandshakeError::Unsupported)));
+}
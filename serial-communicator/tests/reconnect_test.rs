@@ -0,0 +1,147 @@
+#![cfg(unix)]
+
+extern crate serial_communicator;
+
+use std::io::{BufReader, Read, Write};
+use std::os::fd::{FromRawFd, IntoRawFd, OwnedFd};
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use flagfall_protocol::{read_message, write_message, BoardReply, BoardRequest};
+use serial_communicator::frame::{encode_frame, FrameDecoder};
+use serial_communicator::handshake::{BoardInfo, PROTOCOL_VERSION};
+use serialport::{SerialPort, TTYPort};
+
+const SENSOR: u8 = 0x01;
+const HANDSHAKE: u8 = 0x10;
+
+/// Reopens `port` so child processes do not inherit it, which would keep a
+/// pty alive after the test closes it.
+fn _close_on_exec(port: TTYPort) -> TTYPort {
+    // SAFETY: `port` gives up ownership of its descriptor
+    let fd = unsafe { OwnedFd::from_raw_fd(port.into_raw_fd()) };
+    let fd = fd.try_clone().unwrap();
+    // SAFETY: `fd` is a freshly duplicated descriptor owned by nothing else
+    unsafe { TTYPort::from_raw_fd(fd.into_raw_fd()) }
+}
+
+const fn _info() -> BoardInfo {
+    BoardInfo {
+        version: PROTOCOL_VERSION,
+        sensors: true,
+        magnet: true,
+        led_count: 64,
+        events: false,
+        files: 8,
+        ranks: 8,
+    }
+}
+
+/// Opens a pty standing in for the Arduino, answering handshakes and then the
+/// first `requests` other instructions with `reading`. Joining the thread hands back both ends, so
+/// the test decides when the device goes away by dropping them.
+fn _fake_arduino(reading: u64, requests: usize) -> (String, JoinHandle<(TTYPort, TTYPort)>) {
+    let (master, slave) = TTYPort::pair().expect("[reconnect_test::fake_arduino] No pty");
+    let mut master = _close_on_exec(master);
+    let path = slave.name().unwrap();
+    let handle = std::thread::spawn(move || {
+        master.set_timeout(Duration::from_millis(100)).unwrap();
+        let mut decoder = FrameDecoder::default();
+        let mut answered = 0;
+        let mut buf = [0_u8; 64];
+        while answered < requests {
+            match master.read(&mut buf) {
+                Ok(read) => decoder.push(&buf[..read]),
+                Err(e) if e.kind() == std::io::ErrorKind::TimedOut => continue,
+                Err(e) => panic!("[reconnect_test::fake_arduino] {e}"),
+            }
+            while let Some(Ok(frame)) = decoder.next_frame() {
+                if frame.opcode == HANDSHAKE {
+                    master
+                        .write_all(&encode_frame(HANDSHAKE, &_info().to_payload()))
+                        .unwrap();
+                    continue;
+                }
+                master
+                    .write_all(&encode_frame(SENSOR, &reading.to_le_bytes()))
+                    .unwrap();
+                answered += 1;
+            }
+        }
+        (master, slave)
+    });
+    (path, handle)
+}
+
+fn _relink(link: &Path, target: Option<&str>) {
+    let _ = std::fs::remove_file(link);
+    if let Some(target) = target {
+        std::os::unix::fs::symlink(target, link).unwrap();
+    }
+}
+
+fn _request(
+    stdin: &mut ChildStdin,
+    stdout: &mut BufReader<ChildStdout>,
+    request: &BoardRequest,
+) -> BoardReply {
+    write_message(stdin, request).unwrap();
+    read_message(stdout)
+        .unwrap()
+        .expect("[reconnect_test::request] serial-communicator closed its output")
+}
+
+fn _spawn(link: &Path) -> Child {
+    Command::new(env!("CARGO_BIN_EXE_serial-communicator"))
+        .args(["--ready-timeout", "5"])
+        .env("FLAGFALL_SERIAL_PORT", link)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("[reconnect_test::spawn] Cannot start serial-communicator")
+}
+
+#[test]
+fn test_reconnects_after_unplug() {
+    let link = std::env::temp_dir().join(format!("flagfall-reconnect-{}", std::process::id()));
+    let (path, first) = _fake_arduino(0xFFFF, 1);
+    _relink(&link, Some(&path));
+
+    let mut child = _spawn(&link);
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = BufReader::new(child.stdout.take().unwrap());
+
+    assert_eq!(
+        _request(&mut stdin, &mut stdout, &BoardRequest::Scan),
+        BoardReply::Sensors { occupancy: 0xFFFF }
+    );
+
+    // unplug: the device node goes away along with the pty
+    drop(first.join().unwrap());
+    _relink(&link, None);
+    assert!(matches!(
+        _request(&mut stdin, &mut stdout, &BoardRequest::Scan),
+        BoardReply::Disconnected { .. }
+    ));
+
+    // the next request waits until the board is plugged back in
+    write_message(&mut stdin, &BoardRequest::Scan).unwrap();
+    std::thread::sleep(Duration::from_millis(1500));
+    let (path, second) = _fake_arduino(0xFF00, 1);
+    _relink(&link, Some(&path));
+    assert_eq!(
+        read_message::<_, BoardReply>(&mut stdout).unwrap(),
+        Some(BoardReply::Sensors { occupancy: 0xFF00 })
+    );
+    let _ports = second.join().unwrap();
+
+    assert_eq!(
+        _request(&mut stdin, &mut stdout, &BoardRequest::Quit),
+        BoardReply::Done
+    );
+    assert!(child.wait().unwrap().success());
+    _relink(&link, None);
+}
@@ -0,0 +1,206 @@
+extern crate serial_communicator;
+
+use flagfall_protocol::{BoardRequest, MagnetStep, LED_COUNT};
+use serial_communicator::frame::MAX_PAYLOAD;
+use serial_communicator::{LedFrame, Request, RequestConversionError};
+
+fn _instruction(request: &BoardRequest) -> Vec<u8> {
+    match Request::try_from(request) {
+        Ok(request) => request.encode(),
+        Err(e) => panic!("[request_test::instruction] Rejected {request:?}: {e}"),
+    }
+}
+
+fn _round_trip(request: &Request) {
+    assert_eq!(Request::decode(&request.encode()).as_ref(), Ok(request));
+    assert_eq!(request.to_string().parse::<Request>().as_ref(), Ok(request));
+}
+
+#[test]
+fn test_board_request_encoding() {
+    assert_eq!(_instruction(&BoardRequest::Sense), vec![0x01]);
+    assert_eq!(_instruction(&BoardRequest::Scan), vec![0x04]);
+
+    let magnet = _instruction(&BoardRequest::Magnet {
+        steps: vec![MagnetStep {
+            x: 1.5,
+            y: -0.5,
+            magnet: true,
+        }],
+    });
+    let mut expected = vec![0x02];
+    expected.extend_from_slice(&1.5_f32.to_le_bytes());
+    expected.extend_from_slice(&(-0.5_f32).to_le_bytes());
+    expected.push(1);
+    assert_eq!(magnet, expected);
+
+    let mut colours = vec![0; LED_COUNT];
+    colours[0] = 0xFF_A500;
+    let leds = _instruction(&BoardRequest::Leds { colours });
+    assert_eq!(leds.len(), 1 + 3 * LED_COUNT);
+    assert_eq!(&leds[..4], &[0x03, 0xFF, 0xA5, 0x00]);
+}
+
+#[test]
+fn test_board_request_validation() {
+    assert!(Request::try_from(&BoardRequest::Leds {
+        colours: vec![0; LED_COUNT - 1]
+    })
+    .is_err());
+    assert!(Request::try_from(&BoardRequest::Magnet {
+        steps: vec![MagnetStep {
+            x: f32::NAN,
+            y: 0.0,
+            magnet: false,
+        }]
+    })
+    .is_err());
+    // more steps than fit in one frame
+    assert!(Request::try_from(&BoardRequest::Magnet {
+        steps: vec![
+            MagnetStep {
+                x: 1.0,
+                y: 1.0,
+                magnet: false,
+            };
+            MAX_PAYLOAD / 9 + 1
+        ]
+    })
+    .is_err());
+}
+
+#[test]
+fn test_round_trips() {
+    let mut colours = vec![0; LED_COUNT];
+    colours[5] = 0xFF_A500;
+    for request in [
+        Request::Sensor,
+        Request::Scan,
+        Request::Magnet(vec![
+            MagnetStep {
+                x: 1.0,
+                y: 1.0,
+                magnet: false,
+            },
+            MagnetStep {
+                x: -0.5,
+                y: 8.25,
+                magnet: true,
+            },
+        ]),
+        Request::Magnet(Vec::new()),
+        Request::Led(LedFrame::new(colours).unwrap()),
+        Request::Handshake { version: 1 },
+        Request::Subscribe { enabled: true },
+        Request::Subscribe { enabled: false },
+        Request::Ack,
+        Request::Quit,
+    ] {
+        _round_trip(&request);
+    }
+    assert_eq!(
+        Request::Magnet(vec![MagnetStep {
+            x: 1.5,
+            y: 2.0,
+            magnet: true
+        }])
+        .to_string(),
+        "WRITE MAGNET 1.5 2 true"
+    );
+}
+
+#[test]
+fn test_text_errors_point_at_the_token() {
+    let parse = |text: &str| text.parse::<Request>().unwrap_err();
+    assert_eq!(parse("  "), RequestConversionError::EmptyOpSequence);
+    assert_eq!(
+        parse("READ SENSOR"),
+        RequestConversionError::UndefinedOpSequence {
+            token: "READ".to_string(),
+            position: 0
+        }
+    );
+    assert_eq!(
+        parse("WRITE BLINK"),
+        RequestConversionError::UndefinedOpSequence {
+            token: "BLINK".to_string(),
+            position: 1
+        }
+    );
+    assert_eq!(
+        parse("WRITE MAGNET 1 1 false 2 x true"),
+        RequestConversionError::MalformedArgument {
+            token: "x".to_string(),
+            position: 6,
+            expected: "a y coordinate"
+        }
+    );
+    assert_eq!(
+        parse("WRITE MAGNET 1 1 false 2 2"),
+        RequestConversionError::MissingArgument {
+            position: 7,
+            expected: "true or false"
+        }
+    );
+    assert!(matches!(
+        parse("WRITE MAGNET 1 inf false"),
+        RequestConversionError::InvalidStep { index: 0, .. }
+    ));
+    assert_eq!(
+        parse("WRITE SCAN 1"),
+        RequestConversionError::UnexpectedArgument {
+            token: "1".to_string(),
+            position: 2
+        }
+    );
+    assert_eq!(
+        parse("WRITE LED 1 2 3"),
+        RequestConversionError::WrongColourCount { found: 3 }
+    );
+    let mut leds = "WRITE LED".to_string();
+    for _ in 0..LED_COUNT - 1 {
+        leds += " 0";
+    }
+    assert_eq!(
+        parse(&(leds.clone() + " -1")),
+        RequestConversionError::MalformedArgument {
+            token: "-1".to_string(),
+            position: LED_COUNT + 1,
+            expected: "a colour"
+        }
+    );
+    assert_eq!(
+        parse(&(leds + " 16777216")),
+        RequestConversionError::InvalidColour {
+            index: LED_COUNT - 1,
+            colour: 0x100_0000
+        }
+    );
+}
+
+#[test]
+fn test_decode_errors() {
+    assert_eq!(
+        Request::decode(&[]),
+        Err(RequestConversionError::EmptyOpSequence)
+    );
+    assert_eq!(
+        Request::decode(&[0x42]),
+        Err(RequestConversionError::UndefinedOpcode(0x42))
+    );
+    assert_eq!(
+        Request::decode(&[0x01, 0]),
+        Err(RequestConversionError::MalformedPayload {
+            opcode: 0x01,
+            len: 1
+        })
+    );
+    // a partial step, and a magnet flag that is neither 0 nor 1
+    assert!(Request::decode(&[0x02, 0, 0, 0, 0]).is_err());
+    assert!(Request::decode(&[0x02, 0, 0, 0x80, 0x3F, 0, 0, 0x80, 0x3F, 2]).is_err());
+    assert!(Request::decode(&[0x05, 2]).is_err());
+    assert_eq!(
+        Request::decode(&[0x03, 0xFF, 0xFF, 0xFF]),
+        Err(RequestConversionError::WrongColourCount { found: 1 })
+    );
+}
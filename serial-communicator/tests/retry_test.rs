@@ -0,0 +1,205 @@
+extern crate serial_communicator;
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use serial_communicator::frame::{encode_frame, Frame, FrameDecoder};
+use serial_communicator::retry::{exchange, exchange_with, ExchangeError, RetryPolicy};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream};
+
+const SENSOR: u8 = 0x01;
+const MAGNET: u8 = 0x02;
+const LED: u8 = 0x03;
+const SCAN: u8 = 0x04;
+const EVENT: u8 = 0x06;
+const ACK: u8 = 0x20;
+const NAK: u8 = 0x21;
+
+fn _policy() -> RetryPolicy {
+    RetryPolicy {
+        scan_timeout: Duration::from_millis(100),
+        magnet_timeout: Duration::from_millis(100),
+        led_timeout: Duration::from_millis(100),
+        attempts: 3,
+        backoff: Duration::from_millis(10),
+        ..RetryPolicy::default()
+    }
+}
+
+/// Answers each frame it receives with the next of `replies`, where an empty
+/// reply means staying silent. Returns how many frames it received.
+async fn _fake_arduino(mut port: DuplexStream, replies: Vec<Vec<u8>>) -> usize {
+    let mut decoder = FrameDecoder::default();
+    let mut replies = replies.into_iter();
+    let mut received = 0;
+    let mut buf = [0_u8; 64];
+    loop {
+        let read = match port.read(&mut buf).await {
+            Ok(0) | Err(_) => return received,
+            Ok(read) => read,
+        };
+        decoder.push(&buf[..read]);
+        while let Some(Ok(_)) = decoder.next_frame() {
+            received += 1;
+            if let Some(reply) = replies.next() {
+                port.write_all(&reply).await.unwrap();
+            }
+        }
+    }
+}
+
+/// Runs `instruction` against a fake Arduino giving `replies`.
+async fn _exchange(
+    instruction: &[u8],
+    replies: Vec<Vec<u8>>,
+) -> (Result<Frame, ExchangeError>, usize) {
+    let (mut host, arduino) = tokio::io::duplex(1024);
+    let arduino = tokio::spawn(_fake_arduino(arduino, replies));
+    let result = exchange(&mut host, &_policy(), instruction).await;
+    drop(host);
+    (result, arduino.await.unwrap())
+}
+
+#[tokio::test]
+async fn test_resends_after_nak() {
+    let (result, received) = _exchange(
+        &[LED, 0, 0, 0],
+        vec![encode_frame(NAK, &[]), encode_frame(ACK, &[])],
+    )
+    .await;
+    assert_eq!(result.unwrap().opcode, ACK);
+    assert_eq!(received, 2);
+
+    let (result, received) = _exchange(&[LED, 0, 0, 0], vec![encode_frame(NAK, &[]); 3]).await;
+    assert!(matches!(
+        result,
+        Err(ExchangeError::Rejected { attempts: 3 })
+    ));
+    assert_eq!(received, 3);
+}
+
+#[tokio::test]
+async fn test_resends_after_timeout() {
+    let (result, received) =
+        _exchange(&[LED, 0, 0, 0], vec![Vec::new(), encode_frame(ACK, &[])]).await;
+    assert_eq!(result.unwrap().opcode, ACK);
+    assert_eq!(received, 2);
+
+    let (result, received) = _exchange(&[SCAN], Vec::new()).await;
+    assert!(matches!(
+        result,
+        Err(ExchangeError::Timeout { attempts: 3 })
+    ));
+    assert_eq!(received, 3);
+}
+
+#[tokio::test]
+async fn test_magnet_is_not_resent_after_timeout() {
+    let mut instruction = vec![MAGNET];
+    instruction.extend_from_slice(&1.0_f32.to_le_bytes());
+    instruction.extend_from_slice(&1.0_f32.to_le_bytes());
+    instruction.push(1);
+
+    let (result, received) = _exchange(&instruction, Vec::new()).await;
+    assert!(matches!(
+        result,
+        Err(ExchangeError::Timeout { attempts: 1 })
+    ));
+    assert_eq!(received, 1);
+
+    // a NAK means nothing moved, so that is still resent
+    let (result, received) = _exchange(
+        &instruction,
+        vec![encode_frame(NAK, &[]), encode_frame(ACK, &[])],
+    )
+    .await;
+    assert_eq!(result.unwrap().opcode, ACK);
+    assert_eq!(received, 2);
+}
+
+#[tokio::test]
+async fn test_stale_and_corrupt_replies_are_skipped() {
+    let reading = 0xFFFF_0000_0000_FFFF_u64.to_le_bytes();
+    let mut corrupt = encode_frame(SENSOR, &[0; 8]);
+    corrupt[5] ^= 0xFF;
+    let mut reply = encode_frame(ACK, &[]);
+    reply.extend(corrupt);
+    reply.extend(encode_frame(SENSOR, &reading));
+
+    let (result, received) = _exchange(&[SCAN], vec![reply]).await;
+    assert_eq!(
+        result.unwrap(),
+        Frame {
+            opcode: SENSOR,
+            payload: reading.to_vec()
+        }
+    );
+    assert_eq!(received, 1);
+}
+
+#[tokio::test]
+async fn test_events_are_set_aside() {
+    let lifted = encode_frame(EVENT, &[12, 0, 0x10, 0, 0, 0]);
+    let placed = encode_frame(EVENT, &[28, 1, 0x20, 0, 0, 0]);
+    let mut reply = lifted;
+    reply.extend(encode_frame(ACK, &[]));
+    reply.extend(&placed);
+
+    let (mut host, arduino) = tokio::io::duplex(1024);
+    let arduino = tokio::spawn(_fake_arduino(arduino, vec![reply]));
+    let mut decoder = FrameDecoder::default();
+    let mut events = VecDeque::new();
+    let result = exchange_with(
+        &mut host,
+        &mut decoder,
+        &_policy(),
+        &[LED, 0, 0, 0],
+        &mut events,
+    )
+    .await;
+    drop(host);
+    assert_eq!(arduino.await.unwrap(), 1);
+
+    assert_eq!(result.unwrap().opcode, ACK);
+    assert_eq!(
+        events,
+        [Frame {
+            opcode: EVENT,
+            payload: vec![12, 0, 0x10, 0, 0, 0],
+        }]
+    );
+    // what came after the answer is still there for the next read
+    assert_eq!(decoder.pending(), placed.len());
+}
+
+#[tokio::test]
+async fn test_late_answers_are_discarded() {
+    let lifted = encode_frame(EVENT, &[12, 0, 0x10, 0, 0, 0]);
+    let mut late = encode_frame(ACK, &[]);
+    late.extend(&lifted);
+    let (mut host, mut arduino) = tokio::io::duplex(1024);
+    // one answer to an instruction that timed out is decoded, one still waits
+    let mut decoder = FrameDecoder::default();
+    decoder.push(&encode_frame(ACK, &[]));
+    arduino.write_all(&late).await.unwrap();
+
+    let arduino = tokio::spawn(_fake_arduino(
+        arduino,
+        vec![encode_frame(NAK, &[]), encode_frame(ACK, &[])],
+    ));
+    let mut events = VecDeque::new();
+    let result = exchange_with(
+        &mut host,
+        &mut decoder,
+        &_policy(),
+        &[LED, 0, 0, 0],
+        &mut events,
+    )
+    .await;
+    drop(host);
+
+    // the NAK to the first try is seen, so it is tried again
+    assert_eq!(result.unwrap().opcode, ACK);
+    assert_eq!(arduino.await.unwrap(), 2);
+    assert_eq!(events.len(), 1);
+}